@@ -18,6 +18,12 @@ if [ ! -f "$BUILD_DIR/ui" ]; then
     exit 1
 fi
 
+# Check if the clippyctl binary exists in the script's directory
+if [ ! -f "$BUILD_DIR/clippyctl" ]; then
+    echo "Error: 'clippyctl' binary not found. Exiting."
+    exit 1
+fi
+
 # Ensure the destination directory exists, create it if it doesn't
 if [ ! -d "$DEST_DIR" ]; then
     echo "$DEST_DIR does not exist. Creating it..."
@@ -31,9 +37,12 @@ sudo mv "$BUILD_DIR/daemon" "$DEST_DIR/clippy_daemon"
 echo "Installing ui binary to $DEST_DIR..."
 sudo mv "$BUILD_DIR/ui" "$DEST_DIR/clippy_ui"
 
+echo "Installing clippyctl binary to $DEST_DIR..."
+sudo mv "$BUILD_DIR/clippyctl" "$DEST_DIR/clippyctl"
+
 # Make sure the binaries are executable
 echo "Setting execute permissions on the binaries..."
-sudo chmod +x "$DEST_DIR/clippy_daemon" "$DEST_DIR/clippy_ui"
+sudo chmod +x "$DEST_DIR/clippy_daemon" "$DEST_DIR/clippy_ui" "$DEST_DIR/clippyctl"
 
 # Copying service file
 if [ ! -f "./clippy_daemon.service" ]; then
@@ -41,11 +50,23 @@ if [ ! -f "./clippy_daemon.service" ]; then
     exit 1
 fi
 
-sudo cp "./clippy_daemon.service" "/etc/systemd/system"
+# Older versions installed the daemon as a system service running as root
+if [ -f "/etc/systemd/system/clippy_daemon.service" ]; then
+    echo "Removing the system service installed by a previous version..."
+    sudo systemctl disable --now clippy_daemon.service || true
+    sudo rm "/etc/systemd/system/clippy_daemon.service"
+    sudo systemctl daemon-reload
+fi
+
+# The daemon runs as a user service, so it reads your clipboard and
+# the UI finds its socket in your runtime directory
+USER_UNIT_DIR="${XDG_CONFIG_HOME:-$HOME/.config}/systemd/user"
+mkdir -p "$USER_UNIT_DIR"
+cp "./clippy_daemon.service" "$USER_UNIT_DIR"
 
 echo "Installation complete, launching the daemon and the ui ..."
 
-sudo systemctl daemon-reload && sudo systemctl --now enable clippy_daemon.service
+systemctl --user daemon-reload && systemctl --user enable --now clippy_daemon.service
 
 nohup clippy_ui &>/dev/null &
 
@@ -0,0 +1,315 @@
+use clippy::client::DaemonClient;
+use clippy::history::{
+    ClipboardContent, History, HistoryChange, HistoryDelta, HistoryEntry, Selection,
+};
+use clippy::protocol::{Request, Response};
+use clippy::search::SearchMode;
+use clippy::transport::Transport;
+
+use anyhow::{anyhow, Context, Result};
+use clap::{Parser, Subcommand};
+use std::io::Read;
+
+const PREVIEW_LENGTH: usize = 80;
+/// Set by `wl-paste --watch` to tell what the clipboard holds.
+const CLIPBOARD_STATE_ENV_VAR: &str = "CLIPBOARD_STATE";
+
+/// Command line client for the clippy clipboard daemon.
+///
+/// Entries are designated by their index in the history,
+/// 0 being the most recent one, as shown by `list`.
+#[derive(Parser)]
+#[command(version)]
+struct Cli {
+    /// Print JSON instead of plain text.
+    #[arg(long, global = true)]
+    json: bool,
+
+    #[command(subcommand)]
+    command: Command,
+}
+
+#[derive(Subcommand)]
+enum Command {
+    /// List the history entries.
+    List {
+        /// Only show the first entries.
+        #[arg(short = 'n', long)]
+        limit: Option<usize>,
+    },
+    /// Print the full value of an entry.
+    Get { index: usize },
+    /// Put an entry back into the clipboard.
+    Copy {
+        index: usize,
+        /// Put it into the primary selection instead, pasted with a middle click.
+        #[arg(long)]
+        primary: bool,
+    },
+    /// List the entries matching a query, best first.
+    Search {
+        query: String,
+        /// How the query is matched against the entries.
+        #[arg(short, long, value_enum, default_value_t)]
+        mode: SearchMode,
+        /// Only show the first results.
+        #[arg(short = 'n', long)]
+        limit: Option<usize>,
+    },
+    /// Remove entries from the history.
+    Delete {
+        #[arg(required = true)]
+        indices: Vec<usize>,
+    },
+    /// Remove every unpinned entry from the history.
+    Clear,
+    /// Pin an entry so it is never evicted nor cleared.
+    Pin { index: usize },
+    /// Unpin an entry.
+    Unpin { index: usize },
+    /// Flag an entry as sensitive, so it expires sooner.
+    MarkSensitive { index: usize },
+    /// Remove the sensitive flag of an entry.
+    UnmarkSensitive { index: usize },
+    /// Print the history changes as they happen.
+    Watch,
+    /// Show the UI at the mouse cursor, for example from a key binding of the desktop.
+    Show,
+    /// Record the text of the standard input, as in `wl-paste --watch clippyctl store`.
+    /// Nothing is recorded when `CLIPBOARD_STATE` says the clipboard is sensitive or empty,
+    /// or when the input is not UTF-8 text.
+    Store,
+    /// Encrypt the history file with a new key.
+    Rekey {
+        /// Read the new passphrase from the standard input,
+        /// when the key is derived from a passphrase.
+        #[arg(long)]
+        passphrase: bool,
+    },
+}
+
+fn main() -> Result<()> {
+    let cli = Cli::parse();
+    let mut client = DaemonClient::connect(&Transport::daemon())?;
+
+    match cli.command {
+        Command::List { limit } => {
+            let history = fetch_history(&mut client)?;
+            let limit = limit.unwrap_or(history.entries.len());
+            print_entries(history.entries.iter().enumerate().take(limit), cli.json)?;
+        }
+        Command::Get { index } => {
+            let history = fetch_history(&mut client)?;
+            let entry = entry_at(&history, index)?;
+
+            if cli.json {
+                println!("{}", serde_json::to_string(entry)?);
+            } else {
+                match &entry.content {
+                    ClipboardContent::Text(text) => print!("{text}"),
+                    ClipboardContent::Image(image) => println!("{}", image.path.display()),
+                }
+            }
+        }
+        Command::Copy { index, primary } => {
+            let id = entry_at(&fetch_history(&mut client)?, index)?.id;
+            let selection = if primary {
+                Selection::Primary
+            } else {
+                Selection::Clipboard
+            };
+            client.request(&Request::Copy { id, selection })?;
+        }
+        Command::Search { query, mode, limit } => {
+            let results = match client.request(&Request::Search { query, mode, limit })? {
+                Response::SearchResults(results) => results,
+                response => return Err(anyhow!("Unexpected response from daemon: {response:?}")),
+            };
+
+            print_entries(
+                results.iter().map(|result| (result.index, &result.entry)),
+                cli.json,
+            )?;
+        }
+        Command::Delete { indices } => {
+            let history = fetch_history(&mut client)?;
+            let ids = indices
+                .into_iter()
+                .map(|index| Ok(entry_at(&history, index)?.id))
+                .collect::<Result<Vec<u64>>>()?;
+
+            match ids.as_slice() {
+                [id] => client.request(&Request::Delete { id: *id })?,
+                _ => client.request(&Request::DeleteMany { ids })?,
+            };
+        }
+        Command::Clear => {
+            client.request(&Request::ClearHistory)?;
+        }
+        Command::Pin { index } | Command::Unpin { index } => {
+            let pinned = matches!(cli.command, Command::Pin { .. });
+            let id = entry_at(&fetch_history(&mut client)?, index)?.id;
+            client.request(&Request::SetPinned { id, pinned })?;
+        }
+        Command::MarkSensitive { index } | Command::UnmarkSensitive { index } => {
+            let sensitive = matches!(cli.command, Command::MarkSensitive { .. });
+            let id = entry_at(&fetch_history(&mut client)?, index)?.id;
+            client.request(&Request::SetSensitive { id, sensitive })?;
+        }
+        Command::Watch => watch(&mut client, cli.json)?,
+        Command::Show => {
+            client.request(&Request::ShowUi)?;
+        }
+        Command::Store => {
+            let state = std::env::var(CLIPBOARD_STATE_ENV_VAR).unwrap_or_default();
+            if !matches!(state.as_str(), "sensitive" | "clear" | "nil") {
+                let mut bytes = Vec::new();
+                std::io::stdin()
+                    .read_to_end(&mut bytes)
+                    .context("Could not read the standard input.")?;
+
+                // Like the daemon, skip the formats we do not support, such as images
+                match String::from_utf8(bytes) {
+                    Ok(text) => {
+                        client.request(&Request::Store { text })?;
+                    }
+                    Err(_) => eprintln!("The standard input is not UTF-8 text, nothing recorded."),
+                }
+            }
+        }
+        Command::Rekey { passphrase } => {
+            let passphrase = if passphrase {
+                Some(read_passphrase()?)
+            } else {
+                None
+            };
+            client.request(&Request::Rekey { passphrase })?;
+        }
+    }
+
+    Ok(())
+}
+
+/// First line of the standard input, without its line ending.
+fn read_passphrase() -> Result<String> {
+    eprintln!("New passphrase:");
+    let mut passphrase = String::new();
+    std::io::stdin()
+        .read_line(&mut passphrase)
+        .context("Could not read the passphrase.")?;
+
+    Ok(passphrase.trim_end_matches(['\r', '\n']).to_string())
+}
+
+fn fetch_history(client: &mut DaemonClient) -> Result<History> {
+    match client.request(&Request::GetHistory)? {
+        Response::History(history) => Ok(history),
+        response => Err(anyhow!("Unexpected response from daemon: {response:?}")),
+    }
+}
+
+fn entry_at(history: &History, index: usize) -> Result<&HistoryEntry> {
+    history.entries.get(index).ok_or_else(|| {
+        anyhow!(
+            "No entry at index {index}, the history has {} entries.",
+            history.entries.len()
+        )
+    })
+}
+
+fn print_entries<'a>(
+    entries: impl Iterator<Item = (usize, &'a HistoryEntry)>,
+    json: bool,
+) -> Result<()> {
+    if json {
+        let entries: Vec<&HistoryEntry> = entries.map(|(_, entry)| entry).collect();
+        println!("{}", serde_json::to_string(&entries)?);
+    } else {
+        for (index, entry) in entries {
+            println!("{index}\t{}", preview(entry));
+        }
+    }
+
+    Ok(())
+}
+
+/// Single line summary of an entry.
+fn preview(entry: &HistoryEntry) -> String {
+    let pinned = if entry.pinned { "[pinned] " } else { "" };
+    let sensitive = if entry.sensitive { "[sensitive] " } else { "" };
+
+    let value = match &entry.content {
+        ClipboardContent::Text(text) => {
+            let line = text.trim().replace(['\n', '\t'], " ");
+            if line.chars().count() > PREVIEW_LENGTH {
+                let truncated: String = line.chars().take(PREVIEW_LENGTH).collect();
+                format!("{truncated}...")
+            } else {
+                line
+            }
+        }
+        ClipboardContent::Image(image) => format!("[image {}x{}]", image.width, image.height),
+    };
+
+    format!("{pinned}{sensitive}{value}")
+}
+
+/// Follow the history changes until the daemon closes the connection.
+fn watch(client: &mut DaemonClient, json: bool) -> Result<()> {
+    let Response::History(mut history) = client.request(&Request::Subscribe)? else {
+        return Err(anyhow!(
+            "The daemon did not send the history on subscription."
+        ));
+    };
+
+    let mut resyncing = false;
+    while let Some(message) = client.next_message()? {
+        match message {
+            // The history we are waiting for already includes these changes
+            Response::Delta(_) if resyncing => {}
+            Response::Delta(delta) => {
+                if history.apply(&delta) {
+                    print_delta(&delta, json)?;
+                } else {
+                    eprintln!("Missed history changes, resyncing ...");
+                    client.send(&Request::GetHistory)?;
+                    resyncing = true;
+                }
+            }
+            Response::History(new_history) => {
+                history = new_history;
+                resyncing = false;
+                if json {
+                    println!("{}", serde_json::to_string(&history)?);
+                } else {
+                    println!("resync\t{} entries", history.entries.len());
+                }
+            }
+            // Meant for the UI
+            Response::ShowUi { .. } => {}
+            response => eprintln!("Unexpected message from daemon: {response:?}"),
+        }
+    }
+
+    Err(anyhow!("The daemon closed the connection."))
+}
+
+fn print_delta(delta: &HistoryDelta, json: bool) -> Result<()> {
+    if json {
+        println!(
+            "{}",
+            serde_json::to_string(delta).context("Could not serialize history change.")?
+        );
+        return Ok(());
+    }
+
+    match &delta.change {
+        HistoryChange::Insert { index, entry } => println!("insert\t{index}\t{}", preview(entry)),
+        HistoryChange::Remove { id } => println!("remove\t{id}"),
+        HistoryChange::MoveToTop { id } => println!("move-to-top\t{id}"),
+        HistoryChange::Update(entry) => println!("update\t{}\t{}", entry.id, preview(entry)),
+        HistoryChange::Clear => println!("clear"),
+    }
+
+    Ok(())
+}
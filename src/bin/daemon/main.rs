@@ -1,26 +1,33 @@
-mod clipboard_daemon;
-
 use std::sync::Arc;
 
 use anyhow::Result;
-use clipboard_daemon::Clippy;
-
-const UI_SENDING_PORT: u32 = 7878;
-const UI_LISTENING_PORT: u32 = 7879;
+use clap::Parser;
+use clippy::daemon::{Clippy, DaemonArgs};
+use clippy::transport::Transport;
 
 fn main() -> Result<()> {
-
     // Init logging
     tracing_subscriber::fmt::init();
 
-    let clippy = Arc::new(Clippy::new()?);
+    let clippy = Arc::new(Clippy::new(DaemonArgs::parse())?);
+
+    // Reload the config file on SIGHUP
+    #[cfg(unix)]
+    Arc::clone(&clippy).reload_config_on_sighup()?;
+
+    // Prune the expired entries in the background
+    Arc::clone(&clippy).sweep_expired_entries();
+
+    // Show the UI when the global hotkey is pressed
+    Arc::clone(&clippy).listen_for_hotkey()?;
 
     // Spawn the UI listener thread. This works because listen_for_ui expects an Arc<Self>.
-    tracing::info!("Clippy listening for UI requests on 127.0.0.1:{UI_LISTENING_PORT} ...");
-    Arc::clone(&clippy).listen_for_ui();
+    let transport = Transport::daemon();
+    Arc::clone(&clippy).listen_for_ui(&transport)?;
+    tracing::info!("Clippy listening for UI requests on {transport} ...");
 
     // Main thread
-    tracing::info!("Clippy listening for clipboard changes and ready to send to UI on 127.0.0.1:{UI_SENDING_PORT} ...");
+    tracing::info!("Clippy listening for clipboard changes ...");
     clippy.monitor_clipboard_events()?;
 
     Ok(())
@@ -1,39 +1,93 @@
 use crate::config::ClippyConfig;
-use crate::DAEMON_LISTENING_PORT;
-use crate::DAEMON_SENDING_PORT;
+use crate::keybindings::{self, Action, Shortcut};
 use anyhow::{anyhow, Context, Result};
-use arboard::Clipboard;
+use clippy::client::DaemonClient;
+use clippy::clipboard::ClipboardProvider;
+use clippy::history::{self, ClipboardContent, History, HistoryEntry, ImageEntry, Selection};
+use clippy::keys::Hotkey;
+use clippy::paste::{self, PasteInjector};
+use clippy::protocol::{Request, Response};
+use clippy::search::{self, Query, SearchMode, SearchResult};
+use clippy::transport::Transport;
 use eframe::egui;
-use ron::de::from_str;
-use std::io::{Read, Write};
-use std::net::{TcpListener, TcpStream};
+use std::collections::{HashMap, HashSet};
+use std::ops::Range;
+use std::sync::atomic::{AtomicBool, Ordering};
 use std::sync::{Arc, Mutex};
 use std::thread;
+use std::time::{Duration, Instant};
+
+const IMAGE_THUMBNAIL_MAX_HEIGHT: f32 = 120.0;
+const RECONNECT_MIN_DELAY: Duration = Duration::from_millis(500);
+const RECONNECT_MAX_DELAY: Duration = Duration::from_secs(10);
+/// Time the window has to hide before pasting into the previous one.
+const PASTE_DELAY: Duration = Duration::from_millis(150);
+/// How often the window having the focus is remembered, while it is not the UI.
+const FOCUS_TRACKING_INTERVAL: Duration = Duration::from_millis(250);
 
 #[derive(Clone)]
 pub struct ClippyApp {
-    pub history_cache: Arc<Mutex<Vec<String>>>,
+    pub history_cache: Arc<Mutex<History>>,
+    /// Thumbnails of the image entries, keyed by image hash.
+    pub image_textures: Arc<Mutex<HashMap<String, egui::TextureHandle>>>,
+    /// Whether we currently receive history updates from the daemon.
+    pub daemon_connected: Arc<AtomicBool>,
     pub search_query: String,
+    search_cache: Option<SearchCache>,
+    /// Position of the entry selected with the keyboard, among those shown.
+    pub selected: usize,
+    pub scroll_to_selected: bool,
+    /// Ids of the entries picked with Ctrl+click, to delete them together.
+    pub marked: HashSet<u64>,
+    /// Whether the search box should take the focus, when the window opens.
+    pub focus_search: bool,
+    pub window_focused: bool,
+    /// Set when the daemon asks to show the window, until the next frame.
+    pub summon: Arc<Mutex<Option<Summon>>>,
+    /// The window was shown by the hotkey, it is hidden again after a copy.
+    pub summoned: bool,
+    shortcuts: Vec<Shortcut>,
+    /// When the focused window was last remembered, for paste on select.
+    focus_remembered_at: Instant,
+    /// Keystroke of the configuration, `None` if it is invalid.
+    paste_keystroke: Option<Hotkey>,
     pub config: ClippyConfig,
     pub style_needs_update: bool,
+    clipboard: Arc<Mutex<Box<dyn ClipboardProvider>>>,
+    injector: Arc<Mutex<Box<dyn PasteInjector>>>,
 }
 
 impl ClippyApp {
-    pub fn new() -> Self {
-        let empty_cache = Vec::new();
+    pub fn new(clipboard: Box<dyn ClipboardProvider>, injector: Box<dyn PasteInjector>) -> Self {
+        let empty_cache = History::default();
+        let config: ClippyConfig = confy::load("clippy", None).unwrap_or_default();
+        let paste_keystroke = config
+            .paste_keystroke
+            .parse()
+            .inspect_err(|e| tracing::error!("Invalid paste keystroke: {e}"))
+            .ok();
 
-        let clippy = ClippyApp {
+        ClippyApp {
             history_cache: Arc::new(Mutex::new(empty_cache)),
+            image_textures: Arc::new(Mutex::new(HashMap::new())),
+            daemon_connected: Arc::new(AtomicBool::new(false)),
             search_query: String::new(),
-            config: confy::load("clippy", None).unwrap_or_default(),
+            search_cache: None,
+            selected: 0,
+            scroll_to_selected: false,
+            marked: HashSet::new(),
+            focus_search: true,
+            window_focused: false,
+            summon: Arc::new(Mutex::new(None)),
+            summoned: false,
+            shortcuts: config.keybindings.shortcuts(),
+            focus_remembered_at: Instant::now(),
+            paste_keystroke,
+            config,
             style_needs_update: true,
-        };
-
-        if let Err(initial_history_error) = clippy.fill_initial_history() {
-            tracing::error!("An error occured when loading initial history in Clippy UI: {initial_history_error}.");
+            clipboard: Arc::new(Mutex::new(clipboard)),
+            injector: Arc::new(Mutex::new(injector)),
         }
-
-        clippy
     }
 
     /// This method is used inside the UI (preferences)
@@ -45,6 +99,8 @@ impl ClippyApp {
             "dark_mode",
             "max_entry_display_length",
             "enable_search",
+            "search_mode",
+            "paste_on_select",
         ];
 
         if !allowed_settings.contains(&field_name) {
@@ -59,146 +115,559 @@ impl ClippyApp {
         tracing::info!("{field_name} changed in config.");
     }
 
+    /// The entries matching the search query, best first, or why the
+    /// query is invalid. They are only searched again when the query
+    /// or the history changes, and shared with the cache rather than copied.
+    pub fn search_results(&mut self) -> SearchResults {
+        let query = if self.config.enable_search {
+            self.search_query.as_str()
+        } else {
+            ""
+        };
+
+        let history = match self.history_cache.lock() {
+            Ok(history) => history,
+            Err(e) => return Arc::new(Err(format!("Could not acquire history lock: {e}"))),
+        };
+
+        let is_cached = self.search_cache.as_ref().is_some_and(|cache| {
+            cache.query == query
+                && cache.mode == self.config.search_mode
+                && cache.seq == history.seq
+                && cache.len == history.entries.len()
+        });
+
+        if !is_cached {
+            let results = Query::new(query, self.config.search_mode)
+                .map(|query| search::search(&history.entries, &query))
+                .map_err(|e| format!("{e:#}"));
+
+            self.search_cache = Some(SearchCache {
+                query: query.to_string(),
+                mode: self.config.search_mode,
+                seq: history.seq,
+                len: history.entries.len(),
+                results: Arc::new(results),
+            });
+        }
+
+        self.search_cache.as_ref().map_or_else(
+            || Arc::new(Ok(Vec::new())),
+            |cache| Arc::clone(&cache.results),
+        )
+    }
+
+    /// Run the action of the shortcut pressed in this frame, if any,
+    /// on the entries shown in their display order.
+    pub fn handle_keys(&mut self, ctx: &egui::Context, entries: &[&HistoryEntry]) {
+        self.selected = self.selected.min(entries.len().saturating_sub(1));
+        // Only the entries shown can be deleted
+        self.marked
+            .retain(|id| entries.iter().any(|entry| entry.id == *id));
+
+        let typed = ctx
+            .wants_keyboard_input()
+            .then_some(self.search_query.as_str());
+        let Some(action) = keybindings::pressed(ctx, &self.shortcuts, typed) else {
+            return;
+        };
+
+        match action {
+            Action::SelectPrevious => {
+                self.selected = self.selected.saturating_sub(1);
+                self.scroll_to_selected = true;
+            }
+            Action::SelectNext => {
+                self.selected = (self.selected + 1).min(entries.len().saturating_sub(1));
+                self.scroll_to_selected = true;
+            }
+            Action::CopySelected => {
+                if let Some(entry) = entries.get(self.selected) {
+                    self.copy_entry(ctx, entry);
+                }
+            }
+            Action::ToggleMark => {
+                if let Some(entry) = entries.get(self.selected) {
+                    self.toggle_mark(entry.id);
+                }
+            }
+            Action::DeleteSelected => {
+                if !self.marked.is_empty() {
+                    self.delete_marked();
+                } else if let Some(entry) = entries.get(self.selected) {
+                    if let Err(e) = self.delete_entry(entry.id) {
+                        tracing::error!("Could not delete entry {}: {e}", entry.id);
+                    }
+                }
+            }
+            Action::Minimize => ctx.send_viewport_cmd(egui::ViewportCommand::Minimized(true)),
+            Action::QuickPick(position) => {
+                if let Some(entry) = entries.get(position) {
+                    self.selected = position;
+                    self.copy_entry(ctx, entry);
+                }
+            }
+        }
+    }
+
     /// Helper method to display a single history entry.
-    /// It is called within the loop iterating through clipboard history
-    pub fn display_history_entry(&self, ui: &mut egui::Ui, ctx: &egui::Context, value: &str) {
+    /// It is called within the loop iterating through clipboard history,
+    /// `ranges` being the parts of the text matching the search query.
+    pub fn display_history_entry(
+        &mut self,
+        ui: &mut egui::Ui,
+        ctx: &egui::Context,
+        entry: &HistoryEntry,
+        ranges: &[Range<usize>],
+        selected: bool,
+    ) {
+        // Entries marked for deletion are outlined
+        let marked = self.marked.contains(&entry.id);
+        let stroke = if marked {
+            egui::Stroke::new(2.0, ui.visuals().warn_fg_color)
+        } else {
+            ui.visuals().widgets.inactive.bg_stroke
+        };
+
         ui.vertical_centered_justified(|ui| {
-            // We create a short version of the value but
-            // we keep the original to be copied
-            let short_value = if value.len() > self.config.max_entry_display_length {
-                let truncated: String = value
-                    .chars()
-                    .take(self.config.max_entry_display_length)
-                    .collect();
-                format!("{}...", truncated)
-            } else {
-                value.to_string()
+            let response = match &entry.content {
+                ClipboardContent::Text(text) => {
+                    // We create a short version of the value but
+                    // we keep the original to be copied
+                    let short_value = if text.len() > self.config.max_entry_display_length {
+                        let truncated: String = text
+                            .chars()
+                            .take(self.config.max_entry_display_length)
+                            .collect();
+                        format!("{}...", truncated)
+                    } else {
+                        text.to_string()
+                    };
+
+                    ui.add(
+                        egui::Button::new(Self::highlight(ui, &short_value, ranges))
+                            .selected(selected)
+                            .stroke(stroke),
+                    )
+                }
+                ClipboardContent::Image(image) => match self.image_texture(ctx, image) {
+                    Ok(texture) => ui.add(
+                        egui::Button::image(
+                            egui::Image::new(&texture).max_height(IMAGE_THUMBNAIL_MAX_HEIGHT),
+                        )
+                        .selected(selected)
+                        .stroke(stroke),
+                    ),
+                    Err(e) => {
+                        tracing::error!("Could not display image {}: {e}", image.hash);
+                        ui.button(format!("[image {}x{}]", image.width, image.height))
+                    }
+                },
             };
 
-            if ui.button(short_value).clicked() {
-                if let Ok(mut clipboard) = Clipboard::new() {
-                    match clipboard.set_text(value) {
-                        Ok(()) => {
-                            tracing::info!("Successfully set value to clipboard.");
-                        }
-                        Err(e) => {
-                            tracing::error!("Could not set clipboard value on click: {e}");
-                        }
+            let response = response.on_hover_text(Self::entry_details(entry));
+            if selected && self.scroll_to_selected {
+                response.scroll_to_me(None);
+            }
+
+            response.context_menu(|ui| {
+                let label = if entry.pinned { "Unpin" } else { "📌 Pin" };
+                if ui.button(label).clicked() {
+                    if let Err(e) = self.set_pinned(entry.id, !entry.pinned) {
+                        tracing::error!("Could not pin entry {}: {e}", entry.id);
+                    }
+                    ui.close_menu();
+                }
+
+                let label = if entry.sensitive {
+                    "Unmark as sensitive"
+                } else {
+                    "Mark as sensitive"
+                };
+                if ui.button(label).clicked() {
+                    if let Err(e) = self.set_sensitive(entry.id, !entry.sensitive) {
+                        tracing::error!("Could not flag entry {} as sensitive: {e}", entry.id);
+                    }
+                    ui.close_menu();
+                }
+
+                #[cfg(target_os = "linux")]
+                if ui.button("Paste into primary selection").clicked() {
+                    if let Err(e) = self.copy_to_selection(entry.id, Selection::Primary) {
+                        tracing::error!(
+                            "Could not copy entry {} to primary selection: {e}",
+                            entry.id
+                        );
                     }
+                    ui.close_menu();
                 }
 
-                if self.config.minimize_on_copy {
-                    // Minimize after copying
-                    ctx.send_viewport_cmd(egui::ViewportCommand::Minimized(true));
+                ui.separator();
+
+                let label = if marked { "Unselect" } else { "Select" };
+                if ui.button(label).clicked() {
+                    self.toggle_mark(entry.id);
+                    ui.close_menu();
+                }
+
+                if ui.button("🗑 Delete").clicked() {
+                    if let Err(e) = self.delete_entry(entry.id) {
+                        tracing::error!("Could not delete entry {}: {e}", entry.id);
+                    }
+                    ui.close_menu();
                 }
+            });
+
+            // Ctrl+click selects entries to delete instead of copying them
+            if response.clicked() && ui.input(|input| input.modifiers.command) {
+                self.toggle_mark(entry.id);
+            } else if response.clicked() {
+                self.copy_entry(ctx, entry);
             }
             ui.add_space(10.0);
         });
     }
 
-    pub fn listen_for_history_updates(self: Arc<Self>) {
+    fn toggle_mark(&mut self, id: u64) {
+        if !self.marked.remove(&id) {
+            self.marked.insert(id);
+        }
+    }
+
+    /// Delete the entries marked with Ctrl+click.
+    pub fn delete_marked(&mut self) {
+        let ids: Vec<u64> = self.marked.drain().collect();
+
+        if let Err(e) = self.delete_entries(ids) {
+            tracing::error!("Could not delete the selected entries: {e}");
+        }
+    }
+
+    /// Bring the window back, at the position of the screen the daemon gave
+    /// if any, with an empty search box ready to type in.
+    pub fn show_window(&mut self, ctx: &egui::Context, position: Option<(i32, i32)>) {
+        if let Some((x, y)) = position {
+            // The daemon gives physical pixels, the window is placed in points
+            let scale = ctx
+                .input(|input| input.viewport().native_pixels_per_point)
+                .unwrap_or_else(|| ctx.pixels_per_point());
+            ctx.send_viewport_cmd(egui::ViewportCommand::OuterPosition(
+                egui::pos2(x as f32, y as f32) / scale,
+            ));
+        }
+
+        // Remember where to paste before the window takes the focus
+        self.remember_focus();
+
+        ctx.send_viewport_cmd(egui::ViewportCommand::Minimized(false));
+        ctx.send_viewport_cmd(egui::ViewportCommand::Focus);
+
+        self.search_query.clear();
+        self.selected = 0;
+        self.scroll_to_selected = true;
+        self.focus_search = true;
+        self.summoned = true;
+    }
+
+    /// While another window has the focus, remember it from time to time,
+    /// so paste on select goes back to it however the window is shown.
+    pub fn track_focus(&mut self) {
+        if self.focus_remembered_at.elapsed() >= FOCUS_TRACKING_INTERVAL {
+            self.remember_focus();
+        }
+    }
+
+    /// Remember the window having the focus, for paste on select.
+    fn remember_focus(&mut self) {
+        if !self.config.paste_on_select {
+            return;
+        }
+        self.focus_remembered_at = Instant::now();
+
+        let result = self
+            .injector
+            .lock()
+            .map_err(|e| anyhow!("Could not acquire injector lock: {}", e))
+            .and_then(|mut injector| injector.remember_focus());
+        if let Err(e) = result {
+            tracing::error!("Could not remember the focused window: {e}");
+        }
+    }
+
+    /// Put an entry into the clipboard, minimizing the window if configured,
+    /// and paste it into the previous window with paste on select.
+    fn copy_entry(&self, ctx: &egui::Context, entry: &HistoryEntry) {
+        let keystroke = self.paste_keystroke.filter(|_| self.config.paste_on_select);
+
+        let copied = self
+            .clipboard
+            .lock()
+            .map_err(|e| anyhow!("Could not acquire clipboard lock: {}", e))
+            .and_then(|mut clipboard| match keystroke {
+                Some(keystroke) => paste::copy_and_paste(
+                    clipboard.as_mut(),
+                    &entry.content,
+                    Arc::clone(&self.injector),
+                    keystroke,
+                    PASTE_DELAY,
+                )
+                .map(|_| ()),
+                None => clipboard.set_content(Selection::Clipboard, &entry.content),
+            });
+
+        let pasting = match copied {
+            Ok(()) => {
+                tracing::info!("Successfully set value to clipboard.");
+                keystroke.is_some()
+            }
+            Err(e) => {
+                tracing::error!("Could not set clipboard value: {e}");
+                false
+            }
+        };
+
+        if self.config.minimize_on_copy || self.summoned || pasting {
+            // Minimize after copying
+            ctx.send_viewport_cmd(egui::ViewportCommand::Minimized(true));
+        }
+    }
+
+    /// Lay out a text with the matched ranges highlighted,
+    /// ignoring the parts of the ranges past its end.
+    fn highlight(ui: &egui::Ui, text: &str, ranges: &[Range<usize>]) -> egui::text::LayoutJob {
+        let font_id = egui::TextStyle::Button.resolve(ui.style());
+        let normal = egui::TextFormat::simple(font_id, ui.visuals().text_color());
+        let highlighted = egui::TextFormat {
+            color: ui.visuals().strong_text_color(),
+            background: ui.visuals().selection.bg_fill,
+            ..normal.clone()
+        };
+
+        let mut job = egui::text::LayoutJob::default();
+        let mut position = 0;
+        for range in ranges {
+            let start = range.start.clamp(position, text.len());
+            let end = range.end.clamp(start, text.len());
+            if !text.is_char_boundary(start) || !text.is_char_boundary(end) {
+                continue;
+            }
+
+            job.append(&text[position..start], 0.0, normal.clone());
+            job.append(&text[start..end], 0.0, highlighted.clone());
+            position = end;
+        }
+        job.append(&text[position..], 0.0, normal);
+
+        job
+    }
+
+    /// Metadata of an entry, shown when hovering it.
+    fn entry_details(entry: &HistoryEntry) -> String {
+        let size = match &entry.content {
+            ClipboardContent::Image(image) => format!("{}x{}", image.width, image.height),
+            ClipboardContent::Text(_) => format!("{} bytes", entry.byte_size),
+        };
+
+        let source = match entry.source {
+            Selection::Clipboard => "",
+            Selection::Primary => "\nSelected with the mouse",
+        };
+        let sensitive = if entry.sensitive {
+            "\nSensitive, expires soon"
+        } else {
+            ""
+        };
+
+        format!(
+            "{:?}, {size}\nCopied {} time(s), last {}\nFirst copied {}{source}{sensitive}",
+            entry.kind,
+            entry.copy_count,
+            Self::format_age(entry.last_copied),
+            Self::format_age(entry.first_copied),
+        )
+    }
+
+    /// Human readable time elapsed since a unix timestamp.
+    fn format_age(timestamp: u64) -> String {
+        let elapsed = history::now().saturating_sub(timestamp);
+
+        match elapsed {
+            0..60 => "just now".to_string(),
+            60..3600 => format!("{} min ago", elapsed / 60),
+            3600..86400 => format!("{} h ago", elapsed / 3600),
+            _ => format!("{} days ago", elapsed / 86400),
+        }
+    }
+
+    /// Get the thumbnail texture of an image entry, uploading
+    /// it to the GPU the first time it is displayed.
+    fn image_texture(
+        &self,
+        ctx: &egui::Context,
+        image: &ImageEntry,
+    ) -> Result<egui::TextureHandle> {
+        let mut textures = self
+            .image_textures
+            .lock()
+            .map_err(|e| anyhow!("Could not acquire textures lock: {}", e))?;
+
+        if let Some(texture) = textures.get(&image.hash) {
+            return Ok(texture.clone());
+        }
+
+        let image_data = image.load()?;
+        let color_image = egui::ColorImage::from_rgba_unmultiplied(
+            [image_data.width, image_data.height],
+            &image_data.bytes,
+        );
+        let texture = ctx.load_texture(&image.hash, color_image, egui::TextureOptions::LINEAR);
+        textures.insert(image.hash.clone(), texture.clone());
+
+        Ok(texture)
+    }
+
+    /// Keep a connection open to the daemon to receive every change of the history.
+    /// When the connection is lost, for example because the daemon restarted,
+    /// we reconnect and start again from a fresh copy of the history.
+    pub fn subscribe_to_history_updates(self: Arc<Self>) {
         let clippy_app = Arc::clone(&self);
-        thread::spawn(move || -> Result<()> {
-            let listener = TcpListener::bind(format!("127.0.0.1:{DAEMON_LISTENING_PORT}"))
-                .context(format!(
-                    "Could not bind to 127.0.0.1:{DAEMON_LISTENING_PORT} when trying to listen for daemon history updates."
-                ))?;
-
-            tracing::info!("UI server listening on port {DAEMON_LISTENING_PORT} ...");
-
-            for stream in listener.incoming() {
-                match stream {
-                    Ok(mut stream) => {
-                        let mut buffer = Vec::new();
-
-                        stream
-                            .read_to_end(&mut buffer)
-                            .context("Failed to read from stream")?;
-                        let request = String::from_utf8_lossy(&buffer);
-
-                        let mut history = clippy_app
-                            .history_cache
-                            .lock()
-                            .map_err(|e| anyhow!("Could not acquire history lock: {}", e))?;
-
-                        *history =
-                            from_str(&request).context("Failed to parse history with RON")?;
+        thread::spawn(move || {
+            let mut retry_delay = RECONNECT_MIN_DELAY;
+            loop {
+                match clippy_app.follow_history_updates(&mut retry_delay) {
+                    Ok(()) => {
+                        tracing::warn!("The daemon closed the history updates connection ...")
                     }
                     Err(e) => {
-                        tracing::error!(
-                            "Failed to accept connexion on {DAEMON_LISTENING_PORT}: {e} ..."
-                        );
+                        tracing::error!("Could not receive history updates from daemon: {e} ...")
                     }
                 }
+                clippy_app.daemon_connected.store(false, Ordering::Relaxed);
+
+                thread::sleep(retry_delay);
+                retry_delay = (retry_delay * 2).min(RECONNECT_MAX_DELAY);
             }
-            Ok(())
         });
     }
 
-    /// Fetch the initial history from the daemon with a
-    /// TCP request. Uses an empty history if it fails.
-    fn fill_initial_history(&self) -> Result<()> {
-        let request_result = (|| -> Result<String> {
-            let mut stream = TcpStream::connect(format!("127.0.0.1:{DAEMON_SENDING_PORT}"))
-                .context(format!(
-                "Initial history request could not bind to \"127.0.0.1:{DAEMON_SENDING_PORT}\"."
-            ))?;
-
-            stream
-                .write_all("GET_HISTORY\n".as_bytes())
-                .context("Failed to write to stream when trying to get initial history.")?;
+    /// Subscribe to the daemon history updates and apply them to the local
+    /// history until the connection is closed. If we notice a missing delta,
+    /// the whole history is requested again.
+    fn follow_history_updates(&self, retry_delay: &mut Duration) -> Result<()> {
+        let mut client = Self::connect_to_daemon()?;
 
-            // Read the server's response into a string.
-            let mut response = String::new();
-            stream
-                .read_to_string(&mut response)
-                .context("Failed to read from stream when trying to get initial history.")?;
-
-            Ok(response)
-        })();
+        let Response::History(current_history) = client.request(&Request::Subscribe)? else {
+            return Err(anyhow!(
+                "The daemon did not send the history on subscription."
+            ));
+        };
 
-        let mut history = self
+        *self
             .history_cache
             .lock()
-            .map_err(|e| anyhow!("Could not acquire history lock: {}", e))?;
+            .map_err(|e| anyhow!("Could not acquire history lock: {}", e))? = current_history;
 
-        if let Ok(old_history) = request_result {
-            *history =
-                from_str(&old_history).context("Failed to parse initial history with RON")?;
-        } else {
-            *history = from_str("")?;
-            tracing::error!("Could not fetch history from clipboard daemon.\nFalling back to an empty history.\n");
+        self.daemon_connected.store(true, Ordering::Relaxed);
+        *retry_delay = RECONNECT_MIN_DELAY;
+        tracing::info!("Subscribed to history updates from clipboard daemon ...");
+
+        let mut resyncing = false;
+        while let Some(message) = client.next_message()? {
+            let mut history = self
+                .history_cache
+                .lock()
+                .map_err(|e| anyhow!("Could not acquire history lock: {}", e))?;
+
+            match message {
+                // The history we are waiting for already includes these changes
+                Response::Delta(_) if resyncing => {}
+                Response::Delta(delta) => {
+                    if !history.apply(&delta) {
+                        tracing::warn!(
+                            "Missed history deltas (at {}, received {}), resyncing ...",
+                            history.seq,
+                            delta.seq
+                        );
+                        client.send(&Request::GetHistory)?;
+                        resyncing = true;
+                    }
+                }
+                Response::History(new_history) => {
+                    *history = new_history;
+                    resyncing = false;
+                }
+                Response::ShowUi { position } => {
+                    tracing::info!("The daemon asked to show the window ...");
+                    *self
+                        .summon
+                        .lock()
+                        .map_err(|e| anyhow!("Could not acquire summon lock: {}", e))? =
+                        Some(Summon { position });
+                }
+                response => tracing::warn!("Unexpected message from daemon: {response:?}"),
+            }
         }
-        tracing::info!("Successfully loaded initial history from clipboard daemon ...");
+
         Ok(())
     }
 
-    pub fn clear_history(&mut self) -> Result<()> {
-        let mut history = self
-            .history_cache
-            .lock()
-            .map_err(|e| anyhow!("Could not acquire history lock: {}", e))?;
+    /// Open a connection to the daemon, ready to send requests.
+    fn connect_to_daemon() -> Result<DaemonClient> {
+        DaemonClient::connect(&Transport::daemon())
+    }
+
+    /// Ask the daemon to pin or unpin an entry. The local history
+    /// is updated when the daemon sends back the change.
+    pub fn set_pinned(&self, id: u64, pinned: bool) -> Result<()> {
+        Self::connect_to_daemon()?
+            .request(&Request::SetPinned { id, pinned })
+            .context("Pin request failed.")?;
+
+        Ok(())
+    }
 
-        history.clear();
+    /// Ask the daemon to remove an entry from the history.
+    pub fn delete_entry(&self, id: u64) -> Result<()> {
+        Self::connect_to_daemon()?
+            .request(&Request::Delete { id })
+            .context("Delete request failed.")?;
 
-        let request_result = (|| -> Result<String> {
-            let mut stream = TcpStream::connect(format!("127.0.0.1:{DAEMON_SENDING_PORT}"))
-                .context(format!(
-                    "Clear history request could not bind to \"127.0.0.1:{DAEMON_SENDING_PORT}\"."
-                ))?;
+        Ok(())
+    }
+
+    /// Ask the daemon to remove several entries at once.
+    pub fn delete_entries(&self, ids: Vec<u64>) -> Result<()> {
+        Self::connect_to_daemon()?
+            .request(&Request::DeleteMany { ids })
+            .context("Delete request failed.")?;
+
+        Ok(())
+    }
+
+    /// Ask the daemon to flag an entry as sensitive or not.
+    pub fn set_sensitive(&self, id: u64, sensitive: bool) -> Result<()> {
+        Self::connect_to_daemon()?
+            .request(&Request::SetSensitive { id, sensitive })
+            .context("Sensitive request failed.")?;
+
+        Ok(())
+    }
 
-            // Send the RESET_HISTORY request to the server
-            stream
-                .write("RESET_HISTORY\n".as_bytes())
-                .expect("Failed to write to stream when trying to clear history.");
+    /// Ask the daemon to put an entry into a selection. The daemon owns the
+    /// selection afterwards, so it can still be pasted once the UI is closed.
+    #[cfg_attr(not(target_os = "linux"), allow(dead_code))]
+    pub fn copy_to_selection(&self, id: u64, selection: Selection) -> Result<()> {
+        Self::connect_to_daemon()?
+            .request(&Request::Copy { id, selection })
+            .context("Copy request failed.")?;
 
-            // Read the server's response into a string.
-            let mut response = String::new();
-            stream
-                .read_to_string(&mut response)
-                .context("Failed to read from stream when trying to clear history.")?;
-            Ok(response)
-        })();
+        Ok(())
+    }
+
+    /// Ask the daemon to clear the history, pinned entries are kept.
+    pub fn clear_history(&mut self) -> Result<()> {
+        let request_result =
+            Self::connect_to_daemon().and_then(|mut client| client.request(&Request::ClearHistory));
 
         if let Err(e) = request_result {
             tracing::error!("Could not clear history: {e}\n");
@@ -207,3 +676,22 @@ impl ClippyApp {
         Ok(())
     }
 }
+
+/// A request of the daemon to show the window.
+pub struct Summon {
+    /// Where the mouse cursor is on the screen, in physical pixels.
+    pub position: Option<(i32, i32)>,
+}
+
+/// The entries matching a search, best first, or why the query is invalid.
+pub type SearchResults = Arc<Result<Vec<SearchResult>, String>>;
+
+/// Results of the last search, kept until the query or the history changes.
+#[derive(Clone)]
+struct SearchCache {
+    query: String,
+    mode: SearchMode,
+    seq: u64,
+    len: usize,
+    results: SearchResults,
+}
@@ -1,9 +1,12 @@
+use crate::keybindings::KeyBindings;
+use clippy::search::SearchMode;
 use serde::{Deserialize, Serialize};
 
 const DEFAULT_MAX_ENTRY_DISPLAY_LENGTH: usize = 100;
 const DEFAULT_MINIMIZE_ON_COPY: bool = true;
 const DEFAULT_MINIMIZE_ON_CLEAR: bool = true;
 const DEFAULT_ENABLE_SEARCH: bool = true;
+const DEFAULT_PASTE_KEYSTROKE: &str = "Ctrl+V";
 
 #[derive(Clone, Serialize, Deserialize, Debug)]
 pub struct ClippyConfig {
@@ -12,6 +15,20 @@ pub struct ClippyConfig {
     pub minimize_on_copy: bool,
     pub minimize_on_clear: bool,
     pub enable_search: bool,
+    #[serde(default)]
+    pub search_mode: SearchMode,
+    #[serde(default)]
+    pub keybindings: KeyBindings,
+    /// Paste the copied entry into the window used before the UI.
+    #[serde(default)]
+    pub paste_on_select: bool,
+    /// Keystroke pasting in the other window, such as "Ctrl+Shift+V" for terminals.
+    #[serde(default = "default_paste_keystroke")]
+    pub paste_keystroke: String,
+}
+
+fn default_paste_keystroke() -> String {
+    DEFAULT_PASTE_KEYSTROKE.to_string()
 }
 
 impl Default for ClippyConfig {
@@ -22,6 +39,10 @@ impl Default for ClippyConfig {
             minimize_on_copy: DEFAULT_MINIMIZE_ON_COPY,
             minimize_on_clear: DEFAULT_MINIMIZE_ON_CLEAR,
             enable_search: DEFAULT_ENABLE_SEARCH,
+            search_mode: SearchMode::default(),
+            keybindings: KeyBindings::default(),
+            paste_on_select: false,
+            paste_keystroke: default_paste_keystroke(),
         }
     }
 }
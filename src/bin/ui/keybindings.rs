@@ -0,0 +1,189 @@
+//! Keyboard shortcuts of the UI.
+//!
+//! Shortcuts are written as key names joined to their modifiers with `+`,
+//! such as `"ArrowDown"`, `"J"` or `"Ctrl+Enter"`, the key names being
+//! those of `egui::Key`.
+
+use eframe::egui::{self, Key, KeyboardShortcut, Modifiers};
+use serde::{Deserialize, Serialize};
+
+/// What a shortcut does to the entries shown.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Action {
+    SelectPrevious,
+    SelectNext,
+    CopySelected,
+    /// Add the selected entry to the entries to delete, or remove it.
+    ToggleMark,
+    /// Delete the marked entries, or the selected one when none is marked.
+    DeleteSelected,
+    Minimize,
+    /// Copy the entry at this position, starting from 0.
+    QuickPick(usize),
+}
+
+#[derive(Clone, Serialize, Deserialize, Debug)]
+#[serde(default)]
+pub struct KeyBindings {
+    pub select_previous: Vec<String>,
+    pub select_next: Vec<String>,
+    pub copy: Vec<String>,
+    pub mark: Vec<String>,
+    pub delete: Vec<String>,
+    pub minimize: Vec<String>,
+    /// Modifiers held with the digits 1 to 9 to copy one of the first entries.
+    pub quick_pick_modifiers: String,
+}
+
+impl Default for KeyBindings {
+    fn default() -> Self {
+        let keys = |names: &[&str]| names.iter().map(|name| name.to_string()).collect();
+
+        Self {
+            select_previous: keys(&["ArrowUp", "K"]),
+            select_next: keys(&["ArrowDown", "J"]),
+            copy: keys(&["Enter"]),
+            mark: keys(&["Space"]),
+            delete: keys(&["Delete"]),
+            minimize: keys(&["Escape"]),
+            quick_pick_modifiers: "Ctrl".to_string(),
+        }
+    }
+}
+
+const DIGITS: [Key; 9] = [
+    Key::Num1,
+    Key::Num2,
+    Key::Num3,
+    Key::Num4,
+    Key::Num5,
+    Key::Num6,
+    Key::Num7,
+    Key::Num8,
+    Key::Num9,
+];
+
+/// A parsed key binding.
+pub type Shortcut = (KeyboardShortcut, Action);
+
+impl KeyBindings {
+    /// Parse the bindings, the most specific shortcuts first as extra
+    /// modifiers are ignored when matching. Invalid ones are left out.
+    pub fn shortcuts(&self) -> Vec<Shortcut> {
+        let bindings = [
+            (&self.select_previous, Action::SelectPrevious),
+            (&self.select_next, Action::SelectNext),
+            (&self.copy, Action::CopySelected),
+            (&self.mark, Action::ToggleMark),
+            (&self.delete, Action::DeleteSelected),
+            (&self.minimize, Action::Minimize),
+        ];
+
+        let mut shortcuts: Vec<Shortcut> = bindings
+            .into_iter()
+            .flat_map(|(names, action)| {
+                names
+                    .iter()
+                    .filter_map(|name| parse_shortcut(name))
+                    .map(move |shortcut| (shortcut, action))
+            })
+            .collect();
+
+        if let Some(modifiers) = parse_modifiers(&self.quick_pick_modifiers) {
+            shortcuts.extend(DIGITS.iter().enumerate().map(|(position, &key)| {
+                (
+                    KeyboardShortcut::new(modifiers, key),
+                    Action::QuickPick(position),
+                )
+            }));
+        }
+
+        shortcuts
+            .sort_by_key(|(shortcut, _)| std::cmp::Reverse(modifier_count(shortcut.modifiers)));
+        shortcuts
+    }
+}
+
+/// Consume the first shortcut pressed in this frame and return its action.
+/// `typed` is the text of the field the user is typing in, if any:
+/// shortcuts that would edit it are left to the field.
+pub fn pressed(ctx: &egui::Context, shortcuts: &[Shortcut], typed: Option<&str>) -> Option<Action> {
+    ctx.input_mut(|input| {
+        let (shortcut, action) = shortcuts
+            .iter()
+            .filter(|(shortcut, _)| typed.is_none_or(|text| !edits_text(shortcut, text)))
+            .find(|(shortcut, _)| input.consume_shortcut(shortcut))?;
+
+        // The text field would still receive the character of the key
+        if shortcut.logical_key == Key::Space {
+            input
+                .events
+                .retain(|event| !matches!(event, egui::Event::Text(text) if text == " "));
+        }
+        Some(*action)
+    })
+}
+
+/// Parse a shortcut such as `"Ctrl+Shift+K"`, logging the invalid ones.
+fn parse_shortcut(name: &str) -> Option<KeyboardShortcut> {
+    let (modifiers, key) = match name.rsplit_once('+') {
+        Some((modifiers, key)) => (parse_modifiers(modifiers)?, key),
+        None => (Modifiers::NONE, name),
+    };
+
+    match Key::from_name(key.trim()) {
+        Some(key) => Some(KeyboardShortcut::new(modifiers, key)),
+        None => {
+            tracing::error!("Invalid key \"{key}\" in the key binding \"{name}\".");
+            None
+        }
+    }
+}
+
+fn parse_modifiers(names: &str) -> Option<Modifiers> {
+    names
+        .split('+')
+        .map(str::trim)
+        .filter(|name| !name.is_empty())
+        .try_fold(Modifiers::NONE, |modifiers, name| {
+            let modifier = match name.to_lowercase().as_str() {
+                "ctrl" | "control" => Modifiers::CTRL,
+                "shift" => Modifiers::SHIFT,
+                "alt" => Modifiers::ALT,
+                "cmd" | "command" => Modifiers::COMMAND,
+                _ => {
+                    tracing::error!("Invalid modifier \"{name}\" in the key bindings.");
+                    return None;
+                }
+            };
+            Some(modifiers | modifier)
+        })
+}
+
+fn modifier_count(modifiers: Modifiers) -> usize {
+    [
+        modifiers.ctrl,
+        modifiers.shift,
+        modifiers.alt,
+        modifiers.command,
+    ]
+    .into_iter()
+    .filter(|&held| held)
+    .count()
+}
+
+/// Whether a text field holding `text` would use the shortcut to edit it.
+fn edits_text(shortcut: &KeyboardShortcut, text: &str) -> bool {
+    if shortcut.modifiers.ctrl || shortcut.modifiers.command || shortcut.modifiers.alt {
+        return false;
+    }
+
+    match shortcut.logical_key {
+        Key::ArrowUp | Key::ArrowDown | Key::Enter | Key::Escape | Key::PageUp | Key::PageDown => {
+            false
+        }
+        // Nothing to delete in an empty field, and no use for leading whitespace
+        Key::Delete | Key::Backspace | Key::Space => !text.is_empty(),
+        _ => true,
+    }
+}
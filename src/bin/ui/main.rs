@@ -1,19 +1,16 @@
 mod clippy_app;
 mod config;
+mod keybindings;
 mod ui;
 
 use std::sync::Arc;
 
+use clippy::clipboard::ArboardClipboard;
+use clippy::paste;
 use clippy_app::ClippyApp;
 use eframe::egui;
 
-
-
-const DAEMON_LISTENING_PORT: u32 = 7878;
-const DAEMON_SENDING_PORT: u32 = 7879;
-
 fn main() -> eframe::Result<()> {
-
     // Init logging
     tracing_subscriber::fmt::init();
 
@@ -28,11 +25,22 @@ fn main() -> eframe::Result<()> {
         ..Default::default()
     };
 
+    let clipboard = match ArboardClipboard::new() {
+        Ok(clipboard) => clipboard,
+        Err(e) => {
+            tracing::error!("Could not access the clipboard, the UI can not run: {e}");
+            std::process::exit(1);
+        }
+    };
+
     // Create a ClippyApp instance normally (not wrapped in an Arc).
-    let clippy_ui = Arc::new(ClippyApp::new());
+    let clippy_ui = Arc::new(ClippyApp::new(
+        Box::new(clipboard),
+        paste::default_injector(),
+    ));
 
-    // Spawn a background thread that periodically updates the shared history.
-    Arc::clone(&clippy_ui).listen_for_history_updates();
+    // Spawn a background thread that keeps the shared history in sync with the daemon.
+    Arc::clone(&clippy_ui).subscribe_to_history_updates();
 
     tracing::info!("Starting App ...");
 
@@ -43,4 +51,4 @@ fn main() -> eframe::Result<()> {
         // We clone the inner value of Arc<ClippyApp> because Arc<ClippyApp> does not implement eframe::App
         Box::new(move |_cc| Ok(Box::new((*clippy_ui).clone()))),
     )
-}
\ No newline at end of file
+}
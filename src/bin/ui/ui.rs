@@ -1,6 +1,8 @@
 use crate::clippy_app::ClippyApp;
 
+use clippy::search::SearchMode;
 use eframe::egui;
+use std::sync::atomic::Ordering;
 
 impl eframe::App for ClippyApp {
     // Handles UI updates.
@@ -22,6 +24,37 @@ impl eframe::App for ClippyApp {
             ctx.set_visuals(egui::Visuals::light());
         }
 
+        // Show the window when the daemon asks for it, from the global hotkey
+        let summon = self.summon.lock().ok().and_then(|mut summon| summon.take());
+        if let Some(summon) = summon {
+            self.show_window(ctx, summon.position);
+        }
+
+        // The search box takes the focus whenever the window is opened again
+        let window_focused = ctx.input(|input| input.focused);
+        if window_focused && !self.window_focused {
+            self.focus_search = true;
+        } else if !window_focused && self.window_focused {
+            self.summoned = false;
+        }
+        self.window_focused = window_focused;
+        if !window_focused {
+            self.track_focus();
+        }
+
+        // The entries shown, pinned ones first, as navigated with the keyboard
+        let results = self.search_results();
+        let (pinned, unpinned): (Vec<_>, Vec<_>) = match results.as_ref() {
+            Ok(results) => results.iter().partition(|result| result.entry.pinned),
+            Err(_) => Default::default(),
+        };
+        let shown: Vec<_> = pinned
+            .iter()
+            .chain(&unpinned)
+            .map(|result| &result.entry)
+            .collect();
+        self.handle_keys(ctx, &shown);
+
         egui::TopBottomPanel::top("top_panel").show(ctx, |ui| {
             ui.add_space(5.);
 
@@ -43,6 +76,13 @@ impl eframe::App for ClippyApp {
                             self.toggle_config_field("minimize_on_clear");
                         }
 
+                        if ui
+                            .checkbox(&mut self.config.paste_on_select, "Paste on select")
+                            .clicked()
+                        {
+                            self.toggle_config_field("paste_on_select");
+                        }
+
                         if ui
                             .checkbox(
                                 &mut self.config.enable_search,
@@ -89,9 +129,45 @@ impl eframe::App for ClippyApp {
             egui::ScrollArea::vertical().show(ui, |ui| {
                 ui.add_space(10.0);
                 ui.vertical_centered(|ui| {
+                    if !self.daemon_connected.load(Ordering::Relaxed) {
+                        ui.colored_label(
+                            ui.visuals().warn_fg_color,
+                            "Clipboard daemon not reachable, retrying ...",
+                        );
+                        ui.add_space(10.0);
+                    }
+
                     // Search input
                     if self.config.enable_search {
-                        ui.text_edit_singleline(&mut self.search_query);
+                        ui.horizontal(|ui| {
+                            let search = ui.text_edit_singleline(&mut self.search_query);
+                            if search.changed() {
+                                self.selected = 0;
+                                self.scroll_to_selected = true;
+                            }
+                            if self.focus_search {
+                                search.request_focus();
+                                self.focus_search = false;
+                            }
+
+                            let mode = self.config.search_mode;
+                            egui::ComboBox::from_id_salt("search_mode")
+                                .selected_text(format!("{mode:?}"))
+                                .show_ui(ui, |ui| {
+                                    for mode in
+                                        [SearchMode::Text, SearchMode::Fuzzy, SearchMode::Regex]
+                                    {
+                                        ui.selectable_value(
+                                            &mut self.config.search_mode,
+                                            mode,
+                                            format!("{mode:?}"),
+                                        );
+                                    }
+                                });
+                            if self.config.search_mode != mode {
+                                self.toggle_config_field("search_mode");
+                            }
+                        });
                         ui.add_space(10.0);
                     };
 
@@ -108,19 +184,57 @@ impl eframe::App for ClippyApp {
                         }
                         tracing::info!("History cleared.");
                     }
+
+                    // Delete the entries selected with Ctrl+click
+                    if !self.marked.is_empty() {
+                        ui.add_space(5.0);
+                        ui.horizontal(|ui| {
+                            let label = format!("🗑 Delete {} selected", self.marked.len());
+                            if ui.button(label).clicked() {
+                                self.delete_marked();
+                            }
+                            if ui.button("Unselect all").clicked() {
+                                self.marked.clear();
+                            }
+                        });
+                    }
                 });
                 ui.add_space(10.0);
 
-                // Iterate through every value of the history
-                if let Ok(history) = self.history_cache.lock() {
-                    for value in history.iter() {
-                        // Filtering based on search query
-                        if self.search_query.trim().is_empty()
-                            || value.trim().contains(&self.search_query)
-                        {
-                            // Display entry with helper method
-                            self.display_history_entry(ui, ctx, &value);
+                // Iterate through the entries matching the search query, best first,
+                // pinned entries are displayed in their own section first
+                match results.as_ref() {
+                    Ok(_) => {
+                        if !pinned.is_empty() {
+                            ui.label("📌 Pinned");
+                            ui.add_space(5.0);
+                            for (position, result) in pinned.iter().enumerate() {
+                                // Display entry with helper method
+                                self.display_history_entry(
+                                    ui,
+                                    ctx,
+                                    &result.entry,
+                                    &result.ranges,
+                                    position == self.selected,
+                                );
+                            }
+                            ui.separator();
+                            ui.add_space(10.0);
                         }
+
+                        for (position, result) in unpinned.iter().enumerate() {
+                            self.display_history_entry(
+                                ui,
+                                ctx,
+                                &result.entry,
+                                &result.ranges,
+                                pinned.len() + position == self.selected,
+                            );
+                        }
+                        self.scroll_to_selected = false;
+                    }
+                    Err(e) => {
+                        ui.colored_label(ui.visuals().error_fg_color, e);
                     }
                 }
             });
@@ -0,0 +1,56 @@
+use crate::protocol::{read_message, write_message, Request, Response, PROTOCOL_VERSION};
+
+use crate::transport::{Stream, Transport};
+
+use anyhow::{anyhow, Context, Result};
+use std::time::Duration;
+
+/// A connection to the daemon, ready to send requests once the
+/// handshake succeeded.
+pub struct DaemonClient {
+    stream: Stream,
+}
+
+impl DaemonClient {
+    pub fn connect(transport: &Transport) -> Result<Self> {
+        let stream = transport.connect()?;
+
+        let mut client = Self { stream };
+
+        match client.request(&Request::Hello {
+            version: PROTOCOL_VERSION,
+        })? {
+            Response::Hello { version } if version == PROTOCOL_VERSION => Ok(client),
+            response => Err(anyhow!("Unexpected handshake response: {response:?}")),
+        }
+    }
+
+    /// Send a request and wait for its response.
+    /// Error responses are returned as a `DaemonError`.
+    pub fn request(&mut self, request: &Request) -> Result<Response> {
+        write_message(&mut self.stream, request)?;
+
+        read_message::<Response>(&mut self.stream)?
+            .ok_or_else(|| anyhow!("The daemon closed the connection."))?
+            .into_result()
+    }
+
+    /// Send a request without waiting for its response, used on
+    /// subscribed connections where responses and deltas are mixed.
+    pub fn send(&mut self, request: &Request) -> Result<()> {
+        write_message(&mut self.stream, request)
+    }
+
+    /// Give up waiting for the daemon after `timeout`, `None` waits forever.
+    pub fn set_read_timeout(&self, timeout: Option<Duration>) -> Result<()> {
+        self.stream
+            .set_read_timeout(timeout)
+            .context("Could not set the read timeout of the daemon connection.")
+    }
+
+    /// Wait for the next message sent by the daemon.
+    /// Returns `None` when the daemon closed the connection.
+    pub fn next_message(&mut self) -> Result<Option<Response>> {
+        read_message(&mut self.stream)
+    }
+}
@@ -0,0 +1,206 @@
+//! Access to the system clipboard.
+//!
+//! The daemon and the UI go through `ClipboardProvider` instead of using
+//! arboard directly, so they can be driven by a `MemoryClipboard` in tests.
+
+use crate::history::{ClipboardContent, Selection};
+
+use anyhow::{anyhow, Context, Result};
+use arboard::{Clipboard, ImageData};
+use std::collections::{HashMap, VecDeque};
+use std::sync::{Arc, Mutex};
+
+pub trait ClipboardProvider: Send {
+    /// Text held by a selection, `None` if it holds no text.
+    fn get_text(&mut self, selection: Selection) -> Result<Option<String>>;
+
+    /// Image held by a selection, `None` if it holds no image.
+    fn get_image(&mut self, selection: Selection) -> Result<Option<ImageData<'static>>>;
+
+    fn set_text(&mut self, selection: Selection, text: &str) -> Result<()>;
+
+    fn set_image(&mut self, selection: Selection, image: ImageData<'static>) -> Result<()>;
+
+    /// Put a value of the history into a selection, loading images from their file.
+    fn set_content(&mut self, selection: Selection, content: &ClipboardContent) -> Result<()> {
+        match content {
+            ClipboardContent::Text(text) => self.set_text(selection, text),
+            ClipboardContent::Image(image) => self.set_image(selection, image.load()?),
+        }
+    }
+}
+
+/// The system clipboard. The primary selection only exists on Linux,
+/// elsewhere it falls back to the clipboard.
+pub struct ArboardClipboard {
+    clipboard: Clipboard,
+}
+
+impl ArboardClipboard {
+    pub fn new() -> Result<Self> {
+        Ok(Self {
+            clipboard: Clipboard::new().context("Could not create a clipboard instance.")?,
+        })
+    }
+
+    fn get(&mut self, selection: Selection) -> arboard::Get<'_> {
+        #[cfg(target_os = "linux")]
+        if selection == Selection::Primary {
+            use arboard::{GetExtLinux, LinuxClipboardKind};
+            return self.clipboard.get().clipboard(LinuxClipboardKind::Primary);
+        }
+
+        self.clipboard.get()
+    }
+
+    fn set(&mut self, selection: Selection) -> arboard::Set<'_> {
+        #[cfg(target_os = "linux")]
+        if selection == Selection::Primary {
+            use arboard::{LinuxClipboardKind, SetExtLinux};
+            return self.clipboard.set().clipboard(LinuxClipboardKind::Primary);
+        }
+
+        self.clipboard.set()
+    }
+}
+
+impl ClipboardProvider for ArboardClipboard {
+    fn get_text(&mut self, selection: Selection) -> Result<Option<String>> {
+        match self.get(selection).text() {
+            Ok(text) => Ok(Some(text)),
+            Err(arboard::Error::ContentNotAvailable) => Ok(None),
+            Err(e) => Err(e).context("Could not read text from the clipboard."),
+        }
+    }
+
+    fn get_image(&mut self, selection: Selection) -> Result<Option<ImageData<'static>>> {
+        match self.get(selection).image() {
+            Ok(image) => Ok(Some(image)),
+            Err(arboard::Error::ContentNotAvailable) => Ok(None),
+            Err(e) => Err(e).context("Could not read image from the clipboard."),
+        }
+    }
+
+    fn set_text(&mut self, selection: Selection, text: &str) -> Result<()> {
+        self.set(selection)
+            .text(text)
+            .context("Could not write text to the clipboard.")
+    }
+
+    fn set_image(&mut self, selection: Selection, image: ImageData<'static>) -> Result<()> {
+        self.set(selection)
+            .image(image)
+            .context("Could not write image to the clipboard.")
+    }
+}
+
+/// An in-memory clipboard for tests. Clones share the same content, so a
+/// test can keep one to copy values while the code under test owns another.
+#[derive(Clone, Default)]
+pub struct MemoryClipboard {
+    state: Arc<Mutex<MemoryState>>,
+}
+
+#[derive(Default)]
+struct MemoryState {
+    selections: HashMap<Selection, MemoryContent>,
+    /// Errors returned by the next reads, in order.
+    read_failures: VecDeque<String>,
+}
+
+#[derive(Clone)]
+enum MemoryContent {
+    Text(String),
+    Image(ImageData<'static>),
+}
+
+impl MemoryClipboard {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Simulate the user copying some text.
+    pub fn copy_text(&self, selection: Selection, text: &str) {
+        self.put(selection, MemoryContent::Text(text.to_string()));
+    }
+
+    /// Simulate the user copying an image.
+    pub fn copy_image(&self, selection: Selection, image: ImageData<'static>) {
+        self.put(selection, MemoryContent::Image(image));
+    }
+
+    /// Empty a selection.
+    pub fn clear(&self, selection: Selection) {
+        if let Ok(mut state) = self.state.lock() {
+            state.selections.remove(&selection);
+        }
+    }
+
+    /// Make the next read fail with `message`.
+    pub fn fail_next_read(&self, message: &str) {
+        if let Ok(mut state) = self.state.lock() {
+            state.read_failures.push_back(message.to_string());
+        }
+    }
+
+    /// Text currently held by a selection.
+    pub fn text(&self, selection: Selection) -> Option<String> {
+        match self.state.lock().ok()?.selections.get(&selection)? {
+            MemoryContent::Text(text) => Some(text.clone()),
+            MemoryContent::Image(_) => None,
+        }
+    }
+
+    /// Image currently held by a selection.
+    pub fn image(&self, selection: Selection) -> Option<ImageData<'static>> {
+        match self.state.lock().ok()?.selections.get(&selection)? {
+            MemoryContent::Image(image) => Some(image.clone()),
+            MemoryContent::Text(_) => None,
+        }
+    }
+
+    fn put(&self, selection: Selection, content: MemoryContent) {
+        if let Ok(mut state) = self.state.lock() {
+            state.selections.insert(selection, content);
+        }
+    }
+
+    /// Content of a selection, or the next scripted failure.
+    fn read(&self, selection: Selection) -> Result<Option<MemoryContent>> {
+        let mut state = self
+            .state
+            .lock()
+            .map_err(|e| anyhow!("Could not acquire clipboard lock: {}", e))?;
+
+        match state.read_failures.pop_front() {
+            Some(message) => Err(anyhow!(message)),
+            None => Ok(state.selections.get(&selection).cloned()),
+        }
+    }
+}
+
+impl ClipboardProvider for MemoryClipboard {
+    fn get_text(&mut self, selection: Selection) -> Result<Option<String>> {
+        match self.read(selection)? {
+            Some(MemoryContent::Text(text)) => Ok(Some(text)),
+            _ => Ok(None),
+        }
+    }
+
+    fn get_image(&mut self, selection: Selection) -> Result<Option<ImageData<'static>>> {
+        match self.read(selection)? {
+            Some(MemoryContent::Image(image)) => Ok(Some(image)),
+            _ => Ok(None),
+        }
+    }
+
+    fn set_text(&mut self, selection: Selection, text: &str) -> Result<()> {
+        self.copy_text(selection, text);
+        Ok(())
+    }
+
+    fn set_image(&mut self, selection: Selection, image: ImageData<'static>) -> Result<()> {
+        self.copy_image(selection, image);
+        Ok(())
+    }
+}
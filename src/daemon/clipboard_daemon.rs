@@ -0,0 +1,1230 @@
+use crate::daemon::config::{DaemonArgs, DaemonConfig, DuplicatePolicy};
+use crate::daemon::filter::ContentFilter;
+use crate::daemon::hotkey;
+use crate::daemon::retention::RetentionPolicy;
+use crate::daemon::watcher::{self, PollingWatcher, WatcherKind};
+use crate::keys::Hotkey;
+
+use crate::clipboard::{ArboardClipboard, ClipboardProvider};
+use crate::encryption::{self, Cipher, KeySource, Secret};
+use crate::history::{
+    self, ClipboardContent, ContentDigest, ContentIndex, ContentKind, History, HistoryChange,
+    HistoryEntry, ImageEntry, Selection,
+};
+use crate::protocol::{
+    read_message, write_message, ErrorCode, Request, Response, PROTOCOL_VERSION,
+};
+use crate::search::{self, Query};
+use crate::storage::{self, HistoryStore, StorageBackend};
+use crate::transport::{Stream, Transport};
+
+use anyhow::{anyhow, Context, Result};
+use arboard::ImageData;
+use core::panic;
+use sha2::{Digest, Sha256};
+use std::collections::HashMap;
+use std::fs;
+use std::io::BufWriter;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::{Arc, Mutex, MutexGuard, RwLock};
+use std::{thread, time::Duration};
+
+/// Where the history was saved before its location became configurable,
+/// relative to the folder the daemon is started from.
+const LEGACY_HISTORY_FILE_PATH: &str = ".clipboard_history.ron";
+
+const STREAM_MAX_RETRIES: u32 = 5;
+/// A subscriber that does not read its events for this long is dropped.
+const SUBSCRIBER_WRITE_TIMEOUT: Duration = Duration::from_secs(2);
+
+/// Write half of a client connection, shared between the thread
+/// answering its requests and the ones pushing history deltas.
+type Subscriber = Arc<Mutex<Stream>>;
+
+pub struct Clippy {
+    /// Command line options, applied again on top of the config file when reloading it.
+    args: DaemonArgs,
+    config: RwLock<DaemonConfig>,
+    clipboard: Mutex<Box<dyn ClipboardProvider>>,
+    history: Mutex<History>,
+    /// Entry ids by the digest of their value, kept up to date in `commit`
+    /// and locked after the history.
+    content_index: Mutex<ContentIndex>,
+    store: Mutex<Box<dyn HistoryStore>>,
+    /// Changes made to the history since it was last saved.
+    unsaved_changes: Mutex<Vec<HistoryChange>>,
+    /// Set when the changes alone can not bring the stored history
+    /// up to date, so the whole history is written on the next save.
+    full_save_needed: AtomicBool,
+    /// Encrypts the history file, `None` when it is saved in plain text.
+    cipher: RwLock<Option<Arc<Cipher>>>,
+    filter: RwLock<ContentFilter>,
+    /// Digest of what each selection held when it was last read, so a
+    /// value sitting in the clipboard is only recorded once per copy.
+    last_digests: Mutex<HashMap<Selection, ContentDigest>>,
+    next_id: AtomicU64,
+    /// Set when the backup of the history file holds entries the user deleted
+    /// or that expired, or was written with another key, so it is removed
+    /// after the next save. Entries evicted to make room for new ones are
+    /// not secret, the backup keeps them.
+    stale_backup: AtomicBool,
+    subscribers: Mutex<Vec<Subscriber>>,
+}
+
+impl Clippy {
+    pub fn new(args: DaemonArgs) -> Result<Self> {
+        // Instanciate a clipboard object that will be used to access
+        // or update the system clipboard.
+        let clipboard = ArboardClipboard::new()
+            .context("Could not access the clipboard, the listener daemon can not run.")?;
+
+        Self::with_clipboard(args, Box::new(clipboard))
+    }
+
+    /// Create a daemon reading and writing `clipboard` instead of the system clipboard.
+    pub fn with_clipboard(args: DaemonArgs, clipboard: Box<dyn ClipboardProvider>) -> Result<Self> {
+        let config = DaemonConfig::load(&args)?;
+        let cipher = Self::load_cipher(&config)?;
+        let filter = ContentFilter::new(&config)?;
+
+        // We load the old history when instanciating
+        // a new object to ensure history persistance
+        let history_file = config.history_file(config.storage_backend);
+        let history_exists = history_file.exists();
+        let import_source = Self::import_source(&config);
+        let mut store = storage::open(config.storage_backend, history_file)?;
+
+        let (history, recovered) = match &import_source {
+            Some((backend, path)) => {
+                tracing::info!(
+                    "Importing history from \"{}\" to \"{}\" ...",
+                    path.display(),
+                    history_file.display()
+                );
+                Self::load_history(storage::open(*backend, path)?.as_mut(), cipher.as_deref())?
+            }
+            None if history_exists => Self::load_history(store.as_mut(), cipher.as_deref())?,
+            None => (Vec::new(), false),
+        };
+        let encryption_pending = cipher.is_some() && store.has_plain_text()?;
+        let next_id = history.iter().map(|entry| entry.id + 1).max().unwrap_or(0);
+
+        let content_index = ContentIndex::new(&history, config.ignore_whitespace_differences);
+
+        let clippy = Self {
+            args,
+            config: config.into(),
+            clipboard: clipboard.into(),
+            history: History::new(history).into(),
+            content_index: content_index.into(),
+            store: store.into(),
+            unsaved_changes: Mutex::new(Vec::new()),
+            full_save_needed: false.into(),
+            cipher: cipher.into(),
+            filter: filter.into(),
+            last_digests: Mutex::new(HashMap::new()),
+            next_id: next_id.into(),
+            stale_backup: false.into(),
+            subscribers: Mutex::new(Vec::new()),
+        };
+
+        // Do not leave a plain text history behind once encryption is enabled
+        if encryption_pending {
+            tracing::info!("Encrypting the history file ...");
+            clippy.stale_backup.store(true, Ordering::Relaxed);
+        }
+        if encryption_pending || recovered || import_source.is_some() {
+            clippy.full_save_needed.store(true, Ordering::Relaxed);
+            clippy.save_history()?;
+        }
+
+        Ok(clippy)
+    }
+
+    /// Monitor clipboard changes and notify the subscribed UIs on copy.
+    /// The clipboard is read again every time the watcher reports a change.
+    pub fn monitor_clipboard_events(&self) -> Result<()> {
+        let mut consecutive_clipboard_failures = 0;
+
+        let mut watcher_config = self.watcher_config();
+        let mut watcher = watcher::create(watcher_config.0, watcher_config.1);
+        tracing::info!(
+            "Watching the clipboard with the {} backend ...",
+            watcher.name()
+        );
+
+        loop {
+            if let Ok(mut clipboard) = self.clipboard.lock() {
+                let mut failed = false;
+
+                for selection in self.config().selections() {
+                    // Password managers mark what they copy, it is not even read.
+                    // A value we could not check may come from a slow one.
+                    let sensitive = watcher.is_sensitive(selection).unwrap_or_else(|e| {
+                        tracing::warn!(
+                            "Could not check whether the {selection:?} selection is sensitive: {e:#}"
+                        );
+                        true
+                    });
+                    if sensitive {
+                        self.forget_last_content(selection)?;
+                        continue;
+                    }
+
+                    match self.read_clipboard(clipboard.as_mut(), selection) {
+                        // Nothing we can store, the selection is empty
+                        // or holds an unsupported format.
+                        Ok(None) => {}
+                        Ok(Some((content, pixels))) => {
+                            if self.record_copy(content, pixels, selection)? {
+                                self.history_changed();
+                            }
+                        }
+                        // The primary selection is owned by any application the
+                        // user selects text in, failing to read it is not fatal.
+                        Err(primary_error) if selection == Selection::Primary => {
+                            tracing::warn!("Error getting the primary selection: {primary_error}");
+                        }
+                        Err(clipboard_content_error) => {
+                            tracing::error!(
+                                "Error getting the clipboard content: {clipboard_content_error}"
+                            );
+                            failed = true;
+                        }
+                    }
+                }
+
+                if !failed {
+                    consecutive_clipboard_failures = 0;
+                } else {
+                    consecutive_clipboard_failures += 1;
+
+                    if consecutive_clipboard_failures == 3 {
+                        panic!("Error getting the clipboard content 3 times in a row, aborting daemon run.")
+                    }
+
+                    // Read again after a delay, the watcher may not report another change
+                    drop(clipboard);
+                    thread::sleep(self.refresh_rate());
+                    continue;
+                }
+            }
+
+            // The watcher may have been changed by a config reload
+            if self.watcher_config() != watcher_config {
+                watcher_config = self.watcher_config();
+                watcher = watcher::create(watcher_config.0, watcher_config.1);
+                tracing::info!(
+                    "Watching the clipboard with the {} backend ...",
+                    watcher.name()
+                );
+            }
+
+            if let Err(e) = watcher.wait_for_change(self.refresh_rate()) {
+                tracing::error!(
+                    "The {} clipboard watcher failed, polling the clipboard instead: {e:#}",
+                    watcher.name()
+                );
+                watcher = Box::new(PollingWatcher);
+            }
+        }
+    }
+
+    /// Current configuration, it can change when it is reloaded.
+    fn config(&self) -> DaemonConfig {
+        match self.config.read() {
+            Ok(config) => config.clone(),
+            Err(poisoned) => poisoned.into_inner().clone(),
+        }
+    }
+
+    fn refresh_rate(&self) -> Duration {
+        Duration::from_millis(self.config().clipboard_refresh_rate_ms)
+    }
+
+    /// The options the clipboard watcher is created with.
+    fn watcher_config(&self) -> (WatcherKind, bool) {
+        let config = self.config();
+        (config.clipboard_watcher, config.track_primary_selection)
+    }
+
+    /// Read the configuration file again. If the history file moved, the
+    /// current history is saved at the new location. On failure the
+    /// previous configuration is kept.
+    pub fn reload_config(&self) -> Result<()> {
+        let new_config = DaemonConfig::load(&self.args)?;
+
+        let mut config = self
+            .config
+            .write()
+            .map_err(|e| anyhow!("Could not acquire config lock: {}", e))?;
+        let history_moved = (
+            new_config.storage_backend,
+            new_config.history_file(new_config.storage_backend),
+        ) != (
+            config.storage_backend,
+            config.history_file(config.storage_backend),
+        );
+        let encryption_changed =
+            (new_config.encryption, &new_config.key_file) != (config.encryption, &config.key_file);
+        let cipher = if encryption_changed {
+            Some(Self::load_cipher(&new_config)?)
+        } else {
+            None
+        };
+        let filter = ContentFilter::new(&new_config)?;
+        let store = if history_moved {
+            Some(storage::open(
+                new_config.storage_backend,
+                new_config.history_file(new_config.storage_backend),
+            )?)
+        } else {
+            None
+        };
+        *config = new_config;
+        drop(config);
+
+        if let Some(store) = store {
+            *self.lock_store()? = store;
+        }
+
+        *self
+            .filter
+            .write()
+            .map_err(|e| anyhow!("Could not acquire filter lock: {}", e))? = filter;
+
+        if let Some(cipher) = cipher {
+            *self
+                .cipher
+                .write()
+                .map_err(|e| anyhow!("Could not acquire cipher lock: {}", e))? = cipher;
+        }
+
+        if encryption_changed {
+            self.stale_backup.store(true, Ordering::Relaxed);
+        }
+        if history_moved || encryption_changed {
+            self.full_save_needed.store(true, Ordering::Relaxed);
+            self.save_history()
+                .context("Could not save the history at its new location.")?;
+        }
+
+        tracing::info!("Reloaded daemon config: {:?}", self.config());
+        Ok(())
+    }
+
+    /// Reload the configuration every time the daemon receives SIGHUP.
+    #[cfg(unix)]
+    pub fn reload_config_on_sighup(self: Arc<Self>) -> Result<()> {
+        let mut signals = signal_hook::iterator::Signals::new([signal_hook::consts::SIGHUP])
+            .context("Could not register the SIGHUP handler.")?;
+
+        thread::spawn(move || {
+            for _ in signals.forever() {
+                if let Err(e) = self.reload_config() {
+                    tracing::error!("Could not reload daemon config: {e}");
+                }
+            }
+        });
+
+        Ok(())
+    }
+
+    /// Remove the expired entries in a background thread, sweeping
+    /// the history again when the next entry expires.
+    pub fn sweep_expired_entries(self: Arc<Self>) {
+        thread::spawn(move || loop {
+            let policy = RetentionPolicy::new(&self.config());
+
+            match self.remove_expired_entries(&policy) {
+                Ok(true) => self.history_changed(),
+                Ok(false) => {}
+                Err(e) => tracing::error!("Could not remove expired entries: {e}"),
+            }
+
+            let delay = match self.lock_history() {
+                Ok(history) => policy.next_sweep(&history.entries, history::now()),
+                Err(_) => policy.next_sweep(&[], history::now()),
+            };
+            thread::sleep(delay);
+        });
+    }
+
+    /// Remove the entries the policy says are expired.
+    /// Returns whether some were removed.
+    fn remove_expired_entries(&self, policy: &RetentionPolicy) -> Result<bool> {
+        let mut history = self.lock_history()?;
+        let now = history::now();
+
+        let expired: Vec<HistoryEntry> = history
+            .entries
+            .iter()
+            .filter(|entry| policy.is_expired(entry, now))
+            .cloned()
+            .collect();
+
+        if !expired.is_empty() {
+            self.stale_backup.store(true, Ordering::Relaxed);
+        }
+        for entry in &expired {
+            tracing::info!("Entry {} expired, removing it ...", entry.id);
+            Self::remove_entry_files(entry);
+            self.commit(&mut history, HistoryChange::Remove { id: entry.id });
+        }
+
+        Ok(!expired.is_empty())
+    }
+
+    /// Save the history to the file after it was modified.
+    fn history_changed(&self) {
+        // Save new history to file
+        match self.save_history() {
+            Ok(()) => {
+                tracing::info!("Successfully saved history after history change ...");
+            }
+            Err(e) => {
+                tracing::error!(
+                    "An error occured when saving history to file after history change: {e} ..."
+                );
+            }
+        }
+    }
+
+    /// Record a value read from a selection, unless it is still the one read
+    /// last time or the filter excludes it. Returns whether the history changed.
+    fn record_copy(
+        &self,
+        content: ClipboardContent,
+        pixels: Option<ImageData<'static>>,
+        selection: Selection,
+    ) -> Result<bool> {
+        let digest = content.digest(false);
+        let mut last_digests = self
+            .last_digests
+            .lock()
+            .map_err(|e| anyhow!("Could not acquire last digests lock: {}", e))?;
+        if last_digests.insert(selection, digest) == Some(digest) {
+            return Ok(false);
+        }
+        drop(last_digests);
+
+        self.record_allowed(content, pixels, selection)
+    }
+
+    /// Record a value unless the filter excludes it. Returns whether the history changed.
+    fn record_allowed(
+        &self,
+        content: ClipboardContent,
+        pixels: Option<ImageData<'static>>,
+        selection: Selection,
+    ) -> Result<bool> {
+        if let ClipboardContent::Text(text) = &content {
+            let filter = self
+                .filter
+                .read()
+                .map_err(|e| anyhow!("Could not acquire filter lock: {}", e))?;
+
+            if let Some(exclusion) = filter.check(text) {
+                tracing::info!("Ignoring copied text, it {exclusion} ...");
+                return Ok(false);
+            }
+        }
+
+        self.record_content(content, pixels, selection)?;
+        Ok(true)
+    }
+
+    /// Forget what a selection held, so its value is recorded
+    /// again if it is copied back after a sensitive one.
+    fn forget_last_content(&self, selection: Selection) -> Result<()> {
+        if self
+            .last_digests
+            .lock()
+            .map_err(|e| anyhow!("Could not acquire last digests lock: {}", e))?
+            .remove(&selection)
+            .is_some()
+        {
+            tracing::info!("Ignoring the {selection:?} selection, it was marked as sensitive ...");
+        }
+        Ok(())
+    }
+
+    /// Add a value copied into `source` at the top of the history. If the value is
+    /// already in the history, its copy count and last copy time are updated instead.
+    /// The `pixels` of a new image are written to the images folder.
+    fn record_content(
+        &self,
+        mut content: ClipboardContent,
+        pixels: Option<ImageData<'static>>,
+        source: Selection,
+    ) -> Result<()> {
+        let config = self.config();
+        let mut history = self.lock_history()?;
+
+        let mut content_index = self
+            .content_index
+            .lock()
+            .map_err(|e| anyhow!("Could not acquire content index lock: {}", e))?;
+        if content_index.ignores_whitespace() != config.ignore_whitespace_differences {
+            *content_index =
+                ContentIndex::new(&history.entries, config.ignore_whitespace_differences);
+        }
+        let duplicate = content_index.find(&content).and_then(|id| {
+            history.entries.iter().position(|entry| {
+                entry.id == id
+                    && entry
+                        .content
+                        .is_duplicate_of(&content, config.ignore_whitespace_differences)
+            })
+        });
+        drop(content_index);
+
+        if let Some(index) = duplicate {
+            match config.duplicate_policy {
+                DuplicatePolicy::Ignore => return Ok(()),
+                // Identical images share their file, so they keep a single entry
+                DuplicatePolicy::KeepAll if matches!(content, ClipboardContent::Text(_)) => {}
+                _ => {
+                    let mut updated = history.entries[index].clone();
+                    updated.copy_count += 1;
+                    updated.last_copied = history::now();
+                    updated.source = source;
+                    // The text copied last is the one pasted, an image keeps its stored file
+                    if let ClipboardContent::Text(_) = content {
+                        updated.kind = ContentKind::of(&content);
+                        updated.byte_size = content.byte_size();
+                        updated.content = content;
+                    }
+
+                    let id = updated.id;
+                    self.commit(&mut history, HistoryChange::Update(updated));
+                    if index > 0 {
+                        self.commit(&mut history, HistoryChange::MoveToTop { id });
+                    }
+                    return Ok(());
+                }
+            }
+        }
+
+        if let (ClipboardContent::Image(image), Some(pixels)) = (&mut content, pixels) {
+            image.path = self.store_image(&image.hash, pixels)?;
+        }
+
+        // Insert new value at first index
+        let entry = HistoryEntry::new(
+            self.next_id.fetch_add(1, Ordering::Relaxed),
+            content,
+            source,
+        );
+        self.commit(&mut history, HistoryChange::Insert { index: 0, entry });
+
+        // Keep only the wanted number of entries, pinned ones do not count.
+        // The limit may have been lowered by a config reload, hence the loop.
+        let max_history_length = self.config().max_history_length;
+        while history.entries.iter().filter(|entry| !entry.pinned).count() > max_history_length {
+            let Some(oldest) = history.entries.iter().rev().find(|entry| !entry.pinned) else {
+                break;
+            };
+            Self::remove_entry_files(oldest);
+            let id = oldest.id;
+            self.commit(&mut history, HistoryChange::Remove { id });
+        }
+
+        Ok(())
+    }
+
+    /// Read the current value of a selection, text first and then image.
+    /// Returns `None` when the selection is empty, only holds blank text
+    /// or a format we do not support. Images come with their pixels, their
+    /// file is only written once they enter the history.
+    fn read_clipboard(
+        &self,
+        clipboard: &mut dyn ClipboardProvider,
+        selection: Selection,
+    ) -> Result<Option<(ClipboardContent, Option<ImageData<'static>>)>> {
+        match clipboard.get_text(selection)? {
+            Some(text) if text.trim().is_empty() => return Ok(None),
+            Some(text) => return Ok(Some((ClipboardContent::Text(text), None))),
+            None => {}
+        }
+
+        match clipboard.get_image(selection)? {
+            Some(image) => {
+                let mut hasher = Sha256::new();
+                hasher.update((image.width as u64).to_le_bytes());
+                hasher.update((image.height as u64).to_le_bytes());
+                hasher.update(&image.bytes);
+
+                let entry = ImageEntry {
+                    hash: format!("{:x}", hasher.finalize()),
+                    width: image.width,
+                    height: image.height,
+                    // Set when the image is stored
+                    path: PathBuf::new(),
+                };
+                Ok(Some((ClipboardContent::Image(entry), Some(image))))
+            }
+            None => Ok(None),
+        }
+    }
+
+    /// Write a copied image to the images folder as a PNG file named after
+    /// the hash of its content, returning its path. Images that were already
+    /// stored are not written again.
+    fn store_image(&self, hash: &str, image: ImageData) -> Result<PathBuf> {
+        let images_dir = self.config().images_dir();
+        fs::create_dir_all(&images_dir).context(format!(
+            "Could not create the \"{}\" folder.",
+            images_dir.display()
+        ))?;
+
+        // The UI does not run from the same folder as the daemon,
+        // so we keep the absolute path of the image.
+        let path = fs::canonicalize(&images_dir)
+            .context(format!(
+                "Could not resolve the \"{}\" folder.",
+                images_dir.display()
+            ))?
+            .join(format!("{hash}.png"));
+
+        if !path.exists() {
+            let file = fs::File::create(&path)
+                .context(format!("Could not create \"{}\"", path.display()))?;
+
+            let mut encoder = png::Encoder::new(
+                BufWriter::new(file),
+                image.width as u32,
+                image.height as u32,
+            );
+            encoder.set_color(png::ColorType::Rgba);
+            encoder.set_depth(png::BitDepth::Eight);
+
+            encoder
+                .write_header()
+                .and_then(|mut writer| writer.write_image_data(&image.bytes))
+                .context(format!("Could not write image to \"{}\"", path.display()))?;
+
+            tracing::info!("Stored new clipboard image {hash} ...");
+        }
+
+        Ok(path)
+    }
+
+    /// Remove the files stored alongside the history for an entry
+    /// that is no longer part of it.
+    fn remove_entry_files(entry: &HistoryEntry) {
+        if let ClipboardContent::Image(image) = &entry.content {
+            if let Err(e) = fs::remove_file(&image.path) {
+                tracing::warn!(
+                    "Could not remove image file \"{}\": {e}",
+                    image.path.display()
+                );
+            }
+        }
+    }
+
+    /// Pin or unpin an entry. Pinned entries are never evicted nor cleared.
+    /// Returns false if no entry has this id.
+    fn set_pinned(&self, id: u64, pinned: bool) -> Result<bool> {
+        let mut history = self.lock_history()?;
+
+        match history.entries.iter().find(|entry| entry.id == id) {
+            Some(entry) => {
+                let mut updated = entry.clone();
+                updated.pinned = pinned;
+                self.commit(&mut history, HistoryChange::Update(updated));
+                Ok(true)
+            }
+            None => Ok(false),
+        }
+    }
+
+    /// Flag an entry as sensitive or not.
+    /// Returns false if no entry has this id.
+    fn set_sensitive(&self, id: u64, sensitive: bool) -> Result<bool> {
+        let mut history = self.lock_history()?;
+
+        match history.entries.iter().find(|entry| entry.id == id) {
+            Some(entry) => {
+                let mut updated = entry.clone();
+                updated.sensitive = sensitive;
+                self.commit(&mut history, HistoryChange::Update(updated));
+                Ok(true)
+            }
+            None => Ok(false),
+        }
+    }
+
+    /// Remove an entry from the history.
+    /// Returns false if no entry has this id.
+    fn delete_entry(&self, id: u64) -> Result<bool> {
+        Ok(self.delete_entries(&[id])? > 0)
+    }
+
+    /// Remove the entries with these ids, returning how many were found.
+    fn delete_entries(&self, ids: &[u64]) -> Result<usize> {
+        let mut history = self.lock_history()?;
+
+        let removed: Vec<HistoryEntry> = history
+            .entries
+            .iter()
+            .filter(|entry| ids.contains(&entry.id))
+            .cloned()
+            .collect();
+
+        if !removed.is_empty() {
+            self.stale_backup.store(true, Ordering::Relaxed);
+        }
+        for entry in &removed {
+            Self::remove_entry_files(entry);
+            self.commit(&mut history, HistoryChange::Remove { id: entry.id });
+        }
+
+        Ok(removed.len())
+    }
+
+    /// Put the value of an entry back into a selection. The clipboard monitor
+    /// then records it as a new copy. Returns false if no entry has this id.
+    fn copy_entry(&self, id: u64, selection: Selection) -> Result<bool> {
+        let content = match self
+            .lock_history()?
+            .entries
+            .iter()
+            .find(|entry| entry.id == id)
+        {
+            Some(entry) => entry.content.clone(),
+            None => return Ok(false),
+        };
+
+        self.clipboard
+            .lock()
+            .map_err(|e| anyhow!("Could not acquire clipboard lock: {}", e))?
+            .set_content(selection, &content)?;
+
+        Ok(true)
+    }
+
+    /// Listen for directives coming from the UI for example clear_history() or the initial
+    /// history request when starting. This way the UI can stop and start while always
+    /// having an up to date history as long as the clipboard daemon is running.
+    /// Each connection is handled in its own thread and can send several requests.
+    /// We use a simple retry mechanism in case accepting connections fails.
+    pub fn listen_for_ui(self: Arc<Self>, transport: &Transport) -> Result<()> {
+        let listener = transport.bind().context("UI listener could not bind.")?;
+
+        let clippy = Arc::clone(&self);
+        thread::spawn(move || {
+            let mut get_stream_consecutive_failures = 0;
+            loop {
+                match listener.accept() {
+                    Ok(stream) => {
+                        // Reset the failure counter on success.
+                        get_stream_consecutive_failures = 0;
+
+                        let clippy = Arc::clone(&clippy);
+                        thread::spawn(move || {
+                            if let Err(e) = clippy.handle_connection(stream) {
+                                tracing::error!("Error handling UI connection: {e}");
+                            }
+                        });
+                    }
+                    Err(e) => {
+                        tracing::error!(
+                            "Could not get stream from incoming UI connexion: {e}. Retrying..."
+                        );
+                        get_stream_consecutive_failures += 1;
+                        if get_stream_consecutive_failures >= STREAM_MAX_RETRIES {
+                            tracing::error!("Exceeded {STREAM_MAX_RETRIES} consecutive failures. Exiting UI listener thread.");
+
+                            panic!(
+                                "Exceeded {STREAM_MAX_RETRIES} consecutive failures. Exiting UI listener thread.",
+                            );
+                        }
+                        thread::sleep(Duration::from_millis(500));
+                    }
+                }
+            }
+        });
+
+        Ok(())
+    }
+
+    /// Perform the handshake with a client, then answer
+    /// its requests until it closes the connection.
+    fn handle_connection(&self, stream: Stream) -> Result<()> {
+        let mut reader = stream
+            .try_clone()
+            .context("Could not clone the UI connection stream.")?;
+        // Subscribed connections are also written to by the threads changing the
+        // history, the lock makes sure messages are never interleaved.
+        let writer = Arc::new(Mutex::new(stream));
+
+        let result = self.serve_connection(&mut reader, &writer);
+
+        if let Ok(mut subscribers) = self.subscribers.lock() {
+            subscribers.retain(|subscriber| !Arc::ptr_eq(subscriber, &writer));
+        }
+
+        result
+    }
+
+    fn serve_connection(&self, reader: &mut Stream, writer: &Subscriber) -> Result<()> {
+        match read_message::<Request>(reader)? {
+            Some(Request::Hello { version }) if version == PROTOCOL_VERSION => {
+                Self::send(writer, &Response::Hello { version })?;
+            }
+            Some(Request::Hello { version }) => {
+                return Self::send(
+                    writer,
+                    &Response::error(
+                        ErrorCode::UnsupportedVersion,
+                        format!("Protocol version {version} is not supported, expected {PROTOCOL_VERSION}."),
+                    ),
+                );
+            }
+            Some(_) => {
+                return Self::send(
+                    writer,
+                    &Response::error(
+                        ErrorCode::HandshakeRequired,
+                        "The connection must start with a handshake.",
+                    ),
+                );
+            }
+            None => return Ok(()),
+        }
+
+        let mut subscribed = false;
+        loop {
+            let request = match read_message::<Request>(reader) {
+                Ok(Some(request)) => request,
+                Ok(None) => return Ok(()),
+                Err(e) => {
+                    tracing::warn!(
+                        "Unexpected request received, sending back an error to the UI ..."
+                    );
+                    // The stream is out of sync after a bad frame, we can not keep reading it
+                    return Self::send(
+                        writer,
+                        &Response::error(ErrorCode::BadRequest, e.to_string()),
+                    );
+                }
+            };
+
+            if request == Request::Subscribe && !subscribed {
+                tracing::info!("\"Subscribe\" request received, sending history updates to UI ...");
+                self.subscribe(writer)?;
+                subscribed = true;
+                continue;
+            }
+
+            let response = self.handle_request(request).unwrap_or_else(|e| {
+                tracing::error!("Could not handle UI request: {e}");
+                Response::error(ErrorCode::Internal, e.to_string())
+            });
+
+            Self::send(writer, &response)?;
+        }
+    }
+
+    /// Send the current history to a client and register it
+    /// to receive every following change.
+    fn subscribe(&self, writer: &Subscriber) -> Result<()> {
+        // Holding the history lock guarantees no change happens
+        // between the snapshot and the first delta.
+        let history = self.lock_history()?;
+        let mut subscribers = self
+            .subscribers
+            .lock()
+            .map_err(|e| anyhow!("Could not acquire subscribers lock: {}", e))?;
+
+        writer
+            .lock()
+            .map_err(|e| anyhow!("Could not acquire stream lock: {}", e))?
+            .set_write_timeout(Some(SUBSCRIBER_WRITE_TIMEOUT))
+            .context("Could not set the write timeout of the subscriber stream.")?;
+
+        Self::send(writer, &Response::History(history.clone()))?;
+        subscribers.push(Arc::clone(writer));
+
+        Ok(())
+    }
+
+    /// Apply a change to the history and push it to the subscribed clients.
+    /// Taking the locked history makes sure clients receive the changes
+    /// in the order they were made.
+    fn commit(&self, history: &mut History, change: HistoryChange) {
+        match self.unsaved_changes.lock() {
+            Ok(mut changes) => changes.push(change.clone()),
+            Err(_) => self.full_save_needed.store(true, Ordering::Relaxed),
+        }
+        match self.content_index.lock() {
+            Ok(mut content_index) => content_index.apply(&history.entries, &change),
+            Err(_) => tracing::error!("Could not acquire content index lock, index not updated."),
+        }
+        let delta = history.commit(change);
+        self.broadcast(&Response::Delta(delta));
+    }
+
+    /// Push a message to every subscribed client, dropping the ones we can not reach.
+    /// Returns whether a client received it.
+    fn broadcast(&self, response: &Response) -> bool {
+        let Ok(mut subscribers) = self.subscribers.lock() else {
+            tracing::error!("Could not acquire subscribers lock, message not sent.");
+            return false;
+        };
+
+        subscribers.retain(|subscriber| match Self::send(subscriber, response) {
+            Ok(()) => true,
+            Err(e) => {
+                tracing::info!("Dropping UI subscriber: {e}");
+                false
+            }
+        });
+        !subscribers.is_empty()
+    }
+
+    /// Ask the subscribed UIs to show themselves near a position of the screen.
+    /// Returns whether a UI was reached.
+    pub fn show_ui(&self, position: Option<(i32, i32)>) -> bool {
+        self.broadcast(&Response::ShowUi { position })
+    }
+
+    /// Grab the hotkey of the configuration, if any, in a background
+    /// thread and show the UI at the mouse cursor when it is pressed.
+    pub fn listen_for_hotkey(self: Arc<Self>) -> Result<()> {
+        let Some(hotkey) = self.config().hotkey else {
+            return Ok(());
+        };
+        let hotkey: Hotkey = hotkey.parse()?;
+
+        thread::spawn(move || {
+            let result = hotkey::listen(&hotkey, |position| {
+                if !self.show_ui(position) {
+                    tracing::warn!("Hotkey pressed but no UI is running ...");
+                }
+            });
+            if let Err(e) = result {
+                tracing::error!("Global hotkey disabled: {e:#}");
+            }
+        });
+
+        Ok(())
+    }
+
+    fn send(writer: &Subscriber, response: &Response) -> Result<()> {
+        let mut stream = writer
+            .lock()
+            .map_err(|e| anyhow!("Could not acquire stream lock: {}", e))?;
+
+        write_message(&mut *stream, response)
+    }
+
+    fn handle_request(&self, request: Request) -> Result<Response> {
+        match request {
+            Request::Hello { .. } => Ok(Response::error(
+                ErrorCode::BadRequest,
+                "The handshake was already done.",
+            )),
+            Request::Subscribe => Ok(Response::error(
+                ErrorCode::BadRequest,
+                "The connection is already subscribed.",
+            )),
+            Request::GetHistory => {
+                tracing::info!(
+                    "\"GetHistory\" request received, sending current history to UI ..."
+                );
+
+                let history = self.lock_history()?;
+
+                Ok(Response::History(history.clone()))
+            }
+            Request::Search { query, mode, limit } => {
+                tracing::info!("\"Search\" request received, searching the history ...");
+
+                let query = match Query::new(&query, mode) {
+                    Ok(query) => query,
+                    Err(e) => return Ok(Response::error(ErrorCode::BadRequest, format!("{e:#}"))),
+                };
+
+                let mut results = search::search(&self.lock_history()?.entries, &query);
+                if let Some(limit) = limit {
+                    results.truncate(limit);
+                }
+                Ok(Response::SearchResults(results))
+            }
+            Request::ClearHistory => {
+                tracing::info!("\"ClearHistory\" request received, clearing current history ...");
+
+                self.clear_history()
+                    .context("Could not clear history after UI request.")?;
+
+                Ok(Response::Ok)
+            }
+            Request::SetPinned { id, pinned } => {
+                tracing::info!("\"SetPinned\" request received for entry {id} ...");
+
+                let found = self.set_pinned(id, pinned)?;
+                if found {
+                    self.history_changed();
+                }
+                Ok(Self::found_response(found, id))
+            }
+            Request::SetSensitive { id, sensitive } => {
+                tracing::info!("\"SetSensitive\" request received for entry {id} ...");
+
+                let found = self.set_sensitive(id, sensitive)?;
+                if found {
+                    self.history_changed();
+                }
+                Ok(Self::found_response(found, id))
+            }
+            Request::ShowUi => {
+                tracing::info!("\"ShowUi\" request received, summoning the UI ...");
+
+                if self.show_ui(hotkey::pointer_position()) {
+                    Ok(Response::Ok)
+                } else {
+                    Ok(Response::error(ErrorCode::NotFound, "No UI is running."))
+                }
+            }
+            Request::Rekey { passphrase } => {
+                tracing::info!("\"Rekey\" request received, encrypting history with a new key ...");
+
+                match self.rekey(passphrase) {
+                    Ok(()) => Ok(Response::Ok),
+                    Err(e) => Ok(Response::error(ErrorCode::BadRequest, format!("{e:#}"))),
+                }
+            }
+            Request::Store { text } => {
+                tracing::info!("\"Store\" request received, recording text ...");
+
+                // Not a value read by the monitor, which keeps comparing
+                // the clipboard to what it read last
+                if !text.trim().is_empty()
+                    && self.record_allowed(
+                        ClipboardContent::Text(text),
+                        None,
+                        Selection::Clipboard,
+                    )?
+                {
+                    self.history_changed();
+                }
+                Ok(Response::Ok)
+            }
+            Request::Copy { id, selection } => {
+                tracing::info!("\"Copy\" request received for entry {id} ...");
+
+                Ok(Self::found_response(self.copy_entry(id, selection)?, id))
+            }
+            Request::Delete { id } => {
+                tracing::info!("\"Delete\" request received for entry {id} ...");
+
+                let found = self.delete_entry(id)?;
+                if found {
+                    self.history_changed();
+                }
+                Ok(Self::found_response(found, id))
+            }
+            Request::DeleteMany { ids } => {
+                tracing::info!(
+                    "\"DeleteMany\" request received for {} entries ...",
+                    ids.len()
+                );
+
+                if self.delete_entries(&ids)? > 0 {
+                    self.history_changed();
+                }
+                Ok(Response::Ok)
+            }
+        }
+    }
+
+    /// Response to a request targeting a single entry.
+    fn found_response(found: bool, id: u64) -> Response {
+        if found {
+            Response::Ok
+        } else {
+            Response::error(
+                ErrorCode::NotFound,
+                format!("No entry with id {id} in the history."),
+            )
+        }
+    }
+
+    /// Save the changes made to the history since the last save. If they can
+    /// not be saved, the whole history is written the next time. A backup that
+    /// should not outlive the previous version of the history is removed.
+    fn save_history(&self) -> Result<()> {
+        let cipher = self.cipher()?;
+        let history = self.lock_history()?;
+        let mut changes = self
+            .unsaved_changes
+            .lock()
+            .map_err(|e| anyhow!("Could not acquire unsaved changes lock: {}", e))?;
+        let mut store = self.lock_store()?;
+
+        let result = if self.full_save_needed.swap(false, Ordering::Relaxed) {
+            store.save(&history.entries, cipher.as_deref())
+        } else {
+            store.apply(&history.entries, &changes, cipher.as_deref())
+        };
+        changes.clear();
+        if result.is_err() {
+            self.full_save_needed.store(true, Ordering::Relaxed);
+        }
+        result?;
+
+        if self.stale_backup.swap(false, Ordering::Relaxed) {
+            store.remove_backup()?;
+        }
+        Ok(())
+    }
+
+    /// Loads the history from a store. If it can not be read, what can
+    /// be saved of it is recovered. Also returns whether it was recovered.
+    /// Static method.
+    fn load_history(
+        store: &mut dyn HistoryStore,
+        cipher: Option<&Cipher>,
+    ) -> Result<(Vec<HistoryEntry>, bool)> {
+        match store.load(cipher) {
+            Ok(history) => Ok((history, false)),
+            Err(load_error) => {
+                tracing::error!("Could not load history: {load_error:#}, recovering it ...");
+
+                // An encrypted history is only recovered from its backup, the key may just be wrong
+                store
+                    .recover(cipher)
+                    .map(|history| (history, true))
+                    .map_err(|recover_error| load_error.context(recover_error))
+            }
+        }
+    }
+
+    /// Where to import the history from when there is none at its configured
+    /// location yet: the file of the other backend, or the one of older versions.
+    fn import_source(config: &DaemonConfig) -> Option<(StorageBackend, PathBuf)> {
+        if config.history_file(config.storage_backend).exists() {
+            return None;
+        }
+
+        let other_backend = match config.storage_backend {
+            StorageBackend::Ron => StorageBackend::Sqlite,
+            StorageBackend::Sqlite => StorageBackend::Ron,
+        };
+        let other_file = config.history_file(other_backend);
+        if other_file.exists() {
+            return Some((other_backend, other_file.to_path_buf()));
+        }
+
+        Path::new(LEGACY_HISTORY_FILE_PATH)
+            .exists()
+            .then(|| (StorageBackend::Ron, PathBuf::from(LEGACY_HISTORY_FILE_PATH)))
+    }
+
+    fn lock_store(&self) -> Result<MutexGuard<'_, Box<dyn HistoryStore>>> {
+        self.store
+            .lock()
+            .map_err(|e| anyhow!("Could not acquire store lock: {}", e))
+    }
+
+    fn load_cipher(config: &DaemonConfig) -> Result<Option<Arc<Cipher>>> {
+        let secret = config
+            .encryption
+            .load(&config.key_file)
+            .context("Could not load the history key.")?;
+
+        Ok(secret.map(|secret| Arc::new(Cipher::new(secret))))
+    }
+
+    fn cipher(&self) -> Result<Option<Arc<Cipher>>> {
+        self.cipher
+            .read()
+            .map(|cipher| cipher.clone())
+            .map_err(|e| anyhow!("Could not acquire cipher lock: {}", e))
+    }
+
+    /// Encrypt the history with a new key. Random keys are generated, while
+    /// passphrases have to be given. If the history can not be saved with
+    /// the new key, the previous one is stored back.
+    fn rekey(&self, passphrase: Option<String>) -> Result<()> {
+        let config = self.config();
+
+        let secret = match (config.encryption, passphrase) {
+            (KeySource::None, _) => return Err(anyhow!("History encryption is not enabled.")),
+            (KeySource::Passphrase, Some(passphrase)) if !passphrase.is_empty() => {
+                Secret::Passphrase(passphrase)
+            }
+            (KeySource::Passphrase, _) => {
+                return Err(anyhow!(
+                    "A new passphrase is required to re-key the history."
+                ))
+            }
+            (_, Some(_)) => {
+                return Err(anyhow!(
+                    "The history key is not derived from a passphrase, none should be given."
+                ))
+            }
+            _ => Secret::Key(encryption::generate_key()),
+        };
+
+        let mut cipher = self
+            .cipher
+            .write()
+            .map_err(|e| anyhow!("Could not acquire cipher lock: {}", e))?;
+
+        config.encryption.store(&config.key_file, &secret)?;
+        let new_cipher = Arc::new(Cipher::new(secret));
+
+        let history = self.lock_history()?;
+        let mut changes = self
+            .unsaved_changes
+            .lock()
+            .map_err(|e| anyhow!("Could not acquire unsaved changes lock: {}", e))?;
+        let mut store = self.lock_store()?;
+        if let Err(e) = store.save(&history.entries, Some(&new_cipher)) {
+            if let Some(Secret::Key(key)) = cipher.as_ref().map(|cipher| cipher.secret()) {
+                config
+                    .encryption
+                    .store(&config.key_file, &Secret::Key(*key))
+                    .context("Could not restore the previous history key.")?;
+            }
+            return Err(e).context("Could not save the history with the new key.");
+        }
+
+        *cipher = Some(new_cipher);
+        changes.clear();
+        self.full_save_needed.store(false, Ordering::Relaxed);
+        // The backup is still encrypted with the previous key
+        store.remove_backup()?;
+        tracing::info!("Re-keyed the history file ...");
+        Ok(())
+    }
+
+    fn lock_history(&self) -> Result<MutexGuard<'_, History>> {
+        self.history
+            .lock()
+            .map_err(|e| anyhow!("Could not acquire history lock: {}", e))
+    }
+
+    fn clear_history(&self) -> Result<()> {
+        let mut history = self.lock_history()?;
+
+        // Clear history in memory, except for the pinned entries
+        for entry in history.entries.iter().filter(|entry| !entry.pinned) {
+            Self::remove_entry_files(entry);
+        }
+        self.commit(&mut history, HistoryChange::Clear);
+        self.stale_backup.store(true, Ordering::Relaxed);
+        drop(history);
+
+        self.save_history()
+            .context("Could not save the history file after clearing it.")?;
+
+        // We could also clear the current state of the keyboard
+        // self.clipboard.clear()?;
+        Ok(())
+    }
+}
@@ -0,0 +1,259 @@
+use crate::daemon::watcher::WatcherKind;
+use crate::encryption::KeySource;
+use crate::history::Selection;
+use crate::storage::StorageBackend;
+
+use anyhow::{Context, Result};
+use clap::{Parser, ValueEnum};
+use serde::{Deserialize, Serialize};
+use std::path::{Path, PathBuf};
+
+const APP_NAME: &str = "clippy";
+const CONFIG_NAME: &str = "daemon";
+
+const DEFAULT_MAX_HISTORY_LENGTH: usize = 100;
+const DEFAULT_CLIPBOARD_REFRESH_RATE_MS: u64 = 800;
+const DEFAULT_SENSITIVE_ENTRY_TTL_SECS: u64 = 60;
+const HISTORY_FILE_NAME: &str = "history.ron";
+const DATABASE_FILE_NAME: &str = "history.sqlite3";
+const IMAGES_DIR_NAME: &str = "images";
+const KEY_FILE_NAME: &str = "history.key";
+
+/// What happens when a value already in the history is copied again.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize, ValueEnum)]
+#[serde(rename_all = "snake_case")]
+pub enum DuplicatePolicy {
+    /// Move its entry to the top and count the copy.
+    #[default]
+    MoveToTop,
+    /// Add a new entry. Identical images still share a single entry.
+    KeepAll,
+    /// Leave the history as it is.
+    Ignore,
+}
+
+/// Clipboard history daemon. Options given on the command line
+/// override the ones of the configuration file.
+#[derive(Parser, Clone, Debug)]
+#[command(version)]
+pub struct DaemonArgs {
+    /// Configuration file to use instead of the default one.
+    #[arg(long)]
+    pub config: Option<PathBuf>,
+
+    /// File the history is saved to.
+    #[arg(long)]
+    pub history_path: Option<PathBuf>,
+
+    /// How the history is stored.
+    #[arg(long, value_enum)]
+    pub storage_backend: Option<StorageBackend>,
+
+    /// Database the history is saved to, with `--storage-backend sqlite`.
+    #[arg(long)]
+    pub database_path: Option<PathBuf>,
+
+    /// Maximum number of unpinned entries kept in the history.
+    #[arg(long)]
+    pub max_history_length: Option<usize>,
+
+    /// Delay between two reads of the clipboard when it is polled, in milliseconds.
+    #[arg(long)]
+    pub clipboard_refresh_rate_ms: Option<u64>,
+
+    /// How clipboard changes are detected.
+    #[arg(long, value_enum)]
+    pub clipboard_watcher: Option<WatcherKind>,
+
+    /// Also record the text selected with the mouse (Linux only).
+    #[arg(long)]
+    pub track_primary_selection: bool,
+
+    /// Where the key encrypting the history file comes from.
+    #[arg(long, value_enum)]
+    pub encryption: Option<KeySource>,
+
+    /// File holding the history key, with `--encryption key-file`.
+    #[arg(long)]
+    pub key_file: Option<PathBuf>,
+
+    /// Never record the values matching this regular expression,
+    /// on top of the patterns of the configuration file. Can be repeated.
+    #[arg(long = "ignore-pattern", value_name = "REGEX")]
+    pub ignore_patterns: Vec<String>,
+
+    /// Record values looking like passwords, keys or card numbers.
+    #[arg(long)]
+    pub no_detect_secrets: bool,
+
+    /// Remove unpinned entries not copied for this long, in seconds. 0 keeps them.
+    #[arg(long)]
+    pub max_entry_age_secs: Option<u64>,
+
+    /// Remove unpinned entries flagged as sensitive after this long, in seconds.
+    #[arg(long)]
+    pub sensitive_entry_ttl_secs: Option<u64>,
+
+    /// Key combination showing the UI, such as "Ctrl+Alt+V" (X11 only).
+    #[arg(long)]
+    pub hotkey: Option<String>,
+
+    /// What happens when a value already in the history is copied again.
+    #[arg(long, value_enum)]
+    pub duplicate_policy: Option<DuplicatePolicy>,
+
+    /// Tell apart texts that only differ by their whitespace.
+    #[arg(long)]
+    pub no_ignore_whitespace_differences: bool,
+}
+
+#[derive(Clone, Serialize, Deserialize, Debug, PartialEq)]
+#[serde(default)]
+pub struct DaemonConfig {
+    pub history_path: PathBuf,
+    pub storage_backend: StorageBackend,
+    pub database_path: PathBuf,
+    pub max_history_length: usize,
+    pub clipboard_refresh_rate_ms: u64,
+    pub clipboard_watcher: WatcherKind,
+    /// Record the primary selection along with the clipboard.
+    pub track_primary_selection: bool,
+    /// Where the key encrypting the history file comes from, not encrypted by default.
+    pub encryption: KeySource,
+    pub key_file: PathBuf,
+    /// Regular expressions of values that are never recorded.
+    pub ignore_patterns: Vec<String>,
+    /// Do not record values looking like secrets, such as AWS keys,
+    /// JSON web tokens, private keys or credit card numbers.
+    pub detect_secrets: bool,
+    /// Unpinned entries not copied for this long are removed, 0 keeps them forever.
+    pub max_entry_age_secs: u64,
+    /// Shorter lifetime of the entries flagged as sensitive, 0 to use `max_entry_age_secs`.
+    pub sensitive_entry_ttl_secs: u64,
+    /// Global key combination showing the UI at the mouse cursor, read at start.
+    pub hotkey: Option<String>,
+    pub duplicate_policy: DuplicatePolicy,
+    /// Texts that only differ by their whitespace are the same entry,
+    /// which then holds the text copied last.
+    pub ignore_whitespace_differences: bool,
+}
+
+impl Default for DaemonConfig {
+    fn default() -> Self {
+        Self {
+            history_path: default_data_dir().join(HISTORY_FILE_NAME),
+            storage_backend: StorageBackend::default(),
+            database_path: default_data_dir().join(DATABASE_FILE_NAME),
+            max_history_length: DEFAULT_MAX_HISTORY_LENGTH,
+            clipboard_refresh_rate_ms: DEFAULT_CLIPBOARD_REFRESH_RATE_MS,
+            clipboard_watcher: WatcherKind::default(),
+            track_primary_selection: false,
+            encryption: KeySource::default(),
+            key_file: default_data_dir().join(KEY_FILE_NAME),
+            ignore_patterns: Vec::new(),
+            detect_secrets: true,
+            max_entry_age_secs: 0,
+            sensitive_entry_ttl_secs: DEFAULT_SENSITIVE_ENTRY_TTL_SECS,
+            hotkey: None,
+            duplicate_policy: DuplicatePolicy::default(),
+            ignore_whitespace_differences: true,
+        }
+    }
+}
+
+impl DaemonConfig {
+    /// Read the configuration file, creating it with the default values
+    /// if it does not exist, and apply the command line overrides.
+    pub fn load(args: &DaemonArgs) -> Result<Self> {
+        let mut config: DaemonConfig = match &args.config {
+            Some(path) => confy::load_path(path).context(format!(
+                "Could not load config from \"{}\".",
+                path.display()
+            ))?,
+            None => confy::load(APP_NAME, CONFIG_NAME).context("Could not load daemon config.")?,
+        };
+
+        if let Some(history_path) = &args.history_path {
+            config.history_path = history_path.clone();
+        }
+        if let Some(storage_backend) = args.storage_backend {
+            config.storage_backend = storage_backend;
+        }
+        if let Some(database_path) = &args.database_path {
+            config.database_path = database_path.clone();
+        }
+        if let Some(max_history_length) = args.max_history_length {
+            config.max_history_length = max_history_length;
+        }
+        if let Some(clipboard_refresh_rate_ms) = args.clipboard_refresh_rate_ms {
+            config.clipboard_refresh_rate_ms = clipboard_refresh_rate_ms;
+        }
+        if let Some(clipboard_watcher) = args.clipboard_watcher {
+            config.clipboard_watcher = clipboard_watcher;
+        }
+        if args.track_primary_selection {
+            config.track_primary_selection = true;
+        }
+        if let Some(encryption) = args.encryption {
+            config.encryption = encryption;
+        }
+        if let Some(key_file) = &args.key_file {
+            config.key_file = key_file.clone();
+        }
+        config
+            .ignore_patterns
+            .extend(args.ignore_patterns.iter().cloned());
+        if args.no_detect_secrets {
+            config.detect_secrets = false;
+        }
+        if let Some(max_entry_age_secs) = args.max_entry_age_secs {
+            config.max_entry_age_secs = max_entry_age_secs;
+        }
+        if let Some(sensitive_entry_ttl_secs) = args.sensitive_entry_ttl_secs {
+            config.sensitive_entry_ttl_secs = sensitive_entry_ttl_secs;
+        }
+        if let Some(hotkey) = &args.hotkey {
+            config.hotkey = Some(hotkey.clone());
+        }
+        if let Some(duplicate_policy) = args.duplicate_policy {
+            config.duplicate_policy = duplicate_policy;
+        }
+        if args.no_ignore_whitespace_differences {
+            config.ignore_whitespace_differences = false;
+        }
+
+        Ok(config)
+    }
+
+    /// Selections the daemon records.
+    pub fn selections(&self) -> Vec<Selection> {
+        if self.track_primary_selection && cfg!(target_os = "linux") {
+            vec![Selection::Clipboard, Selection::Primary]
+        } else {
+            vec![Selection::Clipboard]
+        }
+    }
+
+    /// File the history is stored in with a backend.
+    pub fn history_file(&self, backend: StorageBackend) -> &Path {
+        match backend {
+            StorageBackend::Ron => &self.history_path,
+            StorageBackend::Sqlite => &self.database_path,
+        }
+    }
+
+    /// Copied images are stored in a folder next to the history file.
+    pub fn images_dir(&self) -> PathBuf {
+        self.history_path
+            .parent()
+            .unwrap_or(Path::new("."))
+            .join(IMAGES_DIR_NAME)
+    }
+}
+
+/// `$XDG_DATA_HOME/clippy` on Linux, or the platform equivalent.
+fn default_data_dir() -> PathBuf {
+    directories::BaseDirs::new()
+        .map(|dirs| dirs.data_dir().join(APP_NAME))
+        .unwrap_or_else(|| PathBuf::from(".").join(APP_NAME))
+}
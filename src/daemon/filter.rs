@@ -0,0 +1,161 @@
+//! Exclusion of sensitive values from the history.
+//!
+//! Before a copied text is recorded, it is matched against the patterns
+//! of the configuration and, unless disabled, against detectors for
+//! common secrets. Values the copying application marked as sensitive
+//! are rejected before reaching the filter.
+
+use crate::daemon::config::DaemonConfig;
+
+use anyhow::{Context, Result};
+use regex::Regex;
+use std::fmt;
+use std::sync::LazyLock;
+
+/// Kind of secret recognized by the built-in detectors.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum SecretKind {
+    AwsAccessKey,
+    Jwt,
+    PrivateKey,
+    CreditCard,
+}
+
+/// Why a value is kept out of the history.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Exclusion {
+    /// It matches one of the configured patterns.
+    Pattern(String),
+    Secret(SecretKind),
+}
+
+static AWS_ACCESS_KEY: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(r"\b(?:AKIA|ASIA|AGPA|AIDA|AROA)[0-9A-Z]{16}\b").unwrap());
+static JWT: LazyLock<Regex> = LazyLock::new(|| {
+    Regex::new(r"\beyJ[A-Za-z0-9_-]{8,}\.eyJ[A-Za-z0-9_-]{8,}\.[A-Za-z0-9_-]{8,}").unwrap()
+});
+static PRIVATE_KEY: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(r"-----BEGIN (?:[A-Z0-9]+ )*PRIVATE KEY(?: BLOCK)?-----").unwrap());
+/// 13 to 19 digits, optionally grouped with spaces or dashes.
+static CARD_NUMBER: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(r"\b\d(?:[ -]?\d){12,18}\b").unwrap());
+
+/// Decides which copied values can be recorded.
+pub struct ContentFilter {
+    detect_secrets: bool,
+    ignore_patterns: Vec<Regex>,
+}
+
+impl ContentFilter {
+    pub fn new(config: &DaemonConfig) -> Result<Self> {
+        let ignore_patterns = config
+            .ignore_patterns
+            .iter()
+            .map(|pattern| {
+                Regex::new(pattern).context(format!("Invalid ignore pattern \"{pattern}\"."))
+            })
+            .collect::<Result<_>>()?;
+
+        Ok(Self {
+            detect_secrets: config.detect_secrets,
+            ignore_patterns,
+        })
+    }
+
+    /// Why a copied text must not be recorded, `None` if it can be.
+    pub fn check(&self, text: &str) -> Option<Exclusion> {
+        if let Some(pattern) = self
+            .ignore_patterns
+            .iter()
+            .find(|pattern| pattern.is_match(text))
+        {
+            return Some(Exclusion::Pattern(pattern.to_string()));
+        }
+
+        if self.detect_secrets {
+            return detect_secret(text).map(Exclusion::Secret);
+        }
+
+        None
+    }
+}
+
+/// Look for a well known kind of secret in a text.
+pub fn detect_secret(text: &str) -> Option<SecretKind> {
+    if PRIVATE_KEY.is_match(text) {
+        Some(SecretKind::PrivateKey)
+    } else if AWS_ACCESS_KEY.is_match(text) {
+        Some(SecretKind::AwsAccessKey)
+    } else if JWT.is_match(text) {
+        Some(SecretKind::Jwt)
+    } else if CARD_NUMBER
+        .find_iter(text)
+        .any(|number| is_card_number(number.as_str()))
+    {
+        Some(SecretKind::CreditCard)
+    } else {
+        None
+    }
+}
+
+/// Whether the digits start like a Visa, Mastercard, American Express or
+/// Discover card of the matching length, and pass the Luhn checksum.
+/// Other runs of digits, such as timestamps or ids, are left alone.
+fn is_card_number(number: &str) -> bool {
+    let digits: Vec<u32> = number.chars().filter_map(|c| c.to_digit(10)).collect();
+    let prefix = |len: usize| {
+        digits
+            .iter()
+            .take(len)
+            .fold(0, |prefix, digit| prefix * 10 + digit)
+    };
+
+    let known_issuer = match digits.len() {
+        13 | 19 => prefix(1) == 4,
+        15 => matches!(prefix(2), 34 | 37),
+        16 => {
+            prefix(1) == 4
+                || (51..=55).contains(&prefix(2))
+                || (2221..=2720).contains(&prefix(4))
+                || prefix(4) == 6011
+                || prefix(2) == 65
+        }
+        _ => false,
+    };
+    if !known_issuer {
+        return false;
+    }
+
+    let sum: u32 = digits
+        .iter()
+        .rev()
+        .enumerate()
+        .map(|(index, &digit)| match (index % 2, digit * 2) {
+            (0, _) => digit,
+            (_, doubled) if doubled > 9 => doubled - 9,
+            (_, doubled) => doubled,
+        })
+        .sum();
+
+    sum.is_multiple_of(10)
+}
+
+impl fmt::Display for Exclusion {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Exclusion::Pattern(pattern) => write!(f, "matches the ignore pattern \"{pattern}\""),
+            Exclusion::Secret(kind) => write!(f, "looks like {kind}"),
+        }
+    }
+}
+
+impl fmt::Display for SecretKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            SecretKind::AwsAccessKey => "an AWS access key",
+            SecretKind::Jwt => "a JSON web token",
+            SecretKind::PrivateKey => "a private key",
+            SecretKind::CreditCard => "a credit card number",
+        })
+    }
+}
@@ -0,0 +1,85 @@
+//! Global hotkey summoning the UI.
+//!
+//! On X11 the daemon grabs a key combination on the root window and, when it
+//! is pressed, asks the subscribed UIs to show themselves at the mouse
+//! cursor. Wayland compositors do not let clients grab keys, there the
+//! compositor can bind `clippyctl show` instead.
+
+use crate::keys::Hotkey;
+
+use anyhow::Result;
+
+/// Grab the hotkey and call `on_press` with the position of the mouse
+/// cursor every time it is pressed. Only returns on errors.
+#[cfg(target_os = "linux")]
+pub fn listen(hotkey: &Hotkey, mut on_press: impl FnMut(Option<(i32, i32)>)) -> Result<()> {
+    use crate::keys;
+    use anyhow::Context;
+    use x11rb::connection::Connection;
+    use x11rb::protocol::xproto::{ConnectionExt as _, GrabMode, ModMask};
+    use x11rb::protocol::Event;
+
+    let (connection, screen) =
+        x11rb::connect(None).context("Could not connect to the X server.")?;
+    let root = connection.setup().roots[screen].root;
+    let keycode = keys::keycode(&connection, hotkey.keysym)?;
+
+    let modifiers = [
+        (hotkey.ctrl, ModMask::CONTROL),
+        (hotkey.shift, ModMask::SHIFT),
+        (hotkey.alt, ModMask::M1),
+        (hotkey.super_key, ModMask::M4),
+    ]
+    .into_iter()
+    .filter(|(held, _)| *held)
+    .fold(ModMask::from(0u16), |mask, (_, modifier)| mask | modifier);
+
+    // The grab only matches the exact modifiers, Caps Lock and Num Lock included
+    for locks in [
+        ModMask::from(0u16),
+        ModMask::LOCK,
+        ModMask::M2,
+        ModMask::LOCK | ModMask::M2,
+    ] {
+        connection
+            .grab_key(
+                false,
+                root,
+                modifiers | locks,
+                keycode,
+                GrabMode::ASYNC,
+                GrabMode::ASYNC,
+            )?
+            .check()
+            .context("Could not grab the hotkey, another application may already use it.")?;
+    }
+
+    loop {
+        if let Event::KeyPress(event) = connection.wait_for_event()? {
+            on_press(Some((event.root_x.into(), event.root_y.into())));
+        }
+    }
+}
+
+#[cfg(not(target_os = "linux"))]
+pub fn listen(_hotkey: &Hotkey, _on_press: impl FnMut(Option<(i32, i32)>)) -> Result<()> {
+    Err(anyhow::anyhow!("Global hotkeys are only supported on X11."))
+}
+
+/// Position of the mouse cursor on the screen, when the display server tells it.
+pub fn pointer_position() -> Option<(i32, i32)> {
+    #[cfg(target_os = "linux")]
+    {
+        use x11rb::connection::Connection;
+        use x11rb::protocol::xproto::ConnectionExt as _;
+
+        let (connection, screen) = x11rb::connect(None).ok()?;
+        let root = connection.setup().roots[screen].root;
+        let pointer = connection.query_pointer(root).ok()?.reply().ok()?;
+
+        Some((pointer.root_x.into(), pointer.root_y.into()))
+    }
+
+    #[cfg(not(target_os = "linux"))]
+    None
+}
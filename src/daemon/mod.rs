@@ -0,0 +1,11 @@
+//! The daemon recording the clipboard history and serving it to clients.
+
+mod clipboard_daemon;
+pub mod config;
+pub mod filter;
+pub mod hotkey;
+pub mod retention;
+pub mod watcher;
+
+pub use clipboard_daemon::Clippy;
+pub use config::{DaemonArgs, DaemonConfig};
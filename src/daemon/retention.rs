@@ -0,0 +1,71 @@
+//! Expiry of old entries.
+//!
+//! An entry expires once it was not copied for `max_entry_age_secs`, or
+//! `sensitive_entry_ttl_secs` when it is flagged as sensitive. Pinned entries
+//! never expire. The daemon sweeps the history in a background thread.
+
+use crate::daemon::config::DaemonConfig;
+use crate::history::HistoryEntry;
+
+use std::time::Duration;
+
+/// Longest time between two sweeps, so configuration changes are picked up.
+const MAX_SWEEP_INTERVAL: Duration = Duration::from_secs(60);
+
+/// How long entries are kept after they were last copied, in seconds.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct RetentionPolicy {
+    max_age: Option<u64>,
+    sensitive_ttl: Option<u64>,
+}
+
+impl RetentionPolicy {
+    /// The policy of a configuration, where a duration of 0 disables expiry.
+    pub fn new(config: &DaemonConfig) -> Self {
+        let enabled = |secs: u64| (secs > 0).then_some(secs);
+
+        Self {
+            max_age: enabled(config.max_entry_age_secs),
+            sensitive_ttl: enabled(config.sensitive_entry_ttl_secs),
+        }
+    }
+
+    /// Unix time after which an entry is expired, `None` if it is kept forever.
+    pub fn expiry(&self, entry: &HistoryEntry) -> Option<u64> {
+        if entry.pinned {
+            return None;
+        }
+
+        let ttl = match (self.max_age, self.sensitive_ttl) {
+            (max_age, Some(sensitive_ttl)) if entry.sensitive => {
+                Some(max_age.map_or(sensitive_ttl, |max_age| max_age.min(sensitive_ttl)))
+            }
+            (max_age, _) => max_age,
+        };
+
+        // A lifetime reaching past the end of time never ends
+        ttl.and_then(|ttl| entry.last_copied.checked_add(ttl))
+    }
+
+    pub fn is_expired(&self, entry: &HistoryEntry, now: u64) -> bool {
+        self.expiry(entry).is_some_and(|expiry| now > expiry)
+    }
+
+    /// How long to wait before sweeping again: until the first of `entries`
+    /// expires, and no longer than the shortest lifetime of a new entry.
+    pub fn next_sweep(&self, entries: &[HistoryEntry], now: u64) -> Duration {
+        let next_expiry = entries
+            .iter()
+            .filter_map(|entry| self.expiry(entry))
+            .map(|expiry| Duration::from_secs(expiry.saturating_add(1).saturating_sub(now)));
+        let shortest_ttl = [self.max_age, self.sensitive_ttl]
+            .into_iter()
+            .flatten()
+            .map(Duration::from_secs);
+
+        next_expiry
+            .chain(shortest_ttl)
+            .fold(MAX_SWEEP_INTERVAL, Duration::min)
+            .max(Duration::from_secs(1))
+    }
+}
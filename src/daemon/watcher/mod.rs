@@ -0,0 +1,111 @@
+//! Detection of clipboard changes.
+//!
+//! The daemon waits on a `ClipboardWatcher` before reading the clipboard again.
+//! On Linux, the X11 and Wayland backends are notified by the display server
+//! when the clipboard owner changes. Everywhere else, or when no display
+//! server supports it, the clipboard is polled at a fixed interval.
+
+mod polling;
+#[cfg(target_os = "linux")]
+mod wayland;
+#[cfg(target_os = "linux")]
+mod x11;
+
+pub use polling::PollingWatcher;
+
+use crate::history::Selection;
+
+use anyhow::Result;
+use clap::ValueEnum;
+use serde::{Deserialize, Serialize};
+use std::time::Duration;
+
+pub trait ClipboardWatcher: Send {
+    /// Name of the backend, for the logs.
+    fn name(&self) -> &'static str;
+
+    /// Block until the clipboard content may have changed.
+    /// `poll_interval` is only used by backends that are not notified of changes.
+    fn wait_for_change(&mut self, poll_interval: Duration) -> Result<()>;
+
+    /// Whether the application owning a selection marked its value as sensitive.
+    /// Backends that can not see the formats offered always return false.
+    fn is_sensitive(&mut self, _selection: Selection) -> Result<bool> {
+        Ok(false)
+    }
+}
+
+/// Format offered along with the value by password managers, so it is not recorded.
+#[cfg(target_os = "linux")]
+const PASSWORD_MANAGER_HINT: &str = "x-kde-passwordManagerHint";
+
+/// Backend used to detect clipboard changes.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize, ValueEnum)]
+#[serde(rename_all = "lowercase")]
+pub enum WatcherKind {
+    /// The first backend supported by the session.
+    #[default]
+    Auto,
+    Wayland,
+    X11,
+    Polling,
+}
+
+/// Create the watcher for `kind`, also reporting changes of the primary
+/// selection if `track_primary` is set. If the wanted backend can not be
+/// used, the clipboard is polled instead.
+pub fn create(kind: WatcherKind, track_primary: bool) -> Box<dyn ClipboardWatcher> {
+    let watcher = match kind {
+        WatcherKind::Auto => detect(track_primary),
+        WatcherKind::Polling => None,
+        #[cfg(target_os = "linux")]
+        WatcherKind::Wayland => connect("Wayland", wayland::WaylandWatcher::connect, track_primary),
+        #[cfg(target_os = "linux")]
+        WatcherKind::X11 => connect("X11", x11::X11Watcher::connect, track_primary),
+        #[cfg(not(target_os = "linux"))]
+        WatcherKind::Wayland | WatcherKind::X11 => {
+            tracing::warn!("The {kind:?} clipboard watcher is only available on Linux.");
+            None
+        }
+    };
+
+    watcher.unwrap_or_else(|| Box::new(PollingWatcher))
+}
+
+/// Try the backends matching the current session, Wayland first
+/// since X11 clients only see the clipboard through XWayland there.
+#[cfg(target_os = "linux")]
+fn detect(track_primary: bool) -> Option<Box<dyn ClipboardWatcher>> {
+    let has_env = |name| std::env::var_os(name).is_some_and(|value| !value.is_empty());
+
+    if has_env("WAYLAND_DISPLAY") {
+        if let Some(watcher) = connect("Wayland", wayland::WaylandWatcher::connect, track_primary) {
+            return Some(watcher);
+        }
+    }
+    if has_env("DISPLAY") {
+        return connect("X11", x11::X11Watcher::connect, track_primary);
+    }
+
+    None
+}
+
+#[cfg(not(target_os = "linux"))]
+fn detect(_track_primary: bool) -> Option<Box<dyn ClipboardWatcher>> {
+    None
+}
+
+#[cfg(target_os = "linux")]
+fn connect<W: ClipboardWatcher + 'static>(
+    name: &str,
+    connect: fn(bool) -> Result<W>,
+    track_primary: bool,
+) -> Option<Box<dyn ClipboardWatcher>> {
+    match connect(track_primary) {
+        Ok(watcher) => Some(Box::new(watcher)),
+        Err(e) => {
+            tracing::warn!("Could not use the {name} clipboard watcher: {e:#}");
+            None
+        }
+    }
+}
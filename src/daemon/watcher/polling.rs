@@ -0,0 +1,18 @@
+use super::ClipboardWatcher;
+
+use anyhow::Result;
+use std::{thread, time::Duration};
+
+/// Reads the clipboard again after a fixed delay, whether it changed or not.
+pub struct PollingWatcher;
+
+impl ClipboardWatcher for PollingWatcher {
+    fn name(&self) -> &'static str {
+        "polling"
+    }
+
+    fn wait_for_change(&mut self, poll_interval: Duration) -> Result<()> {
+        thread::sleep(poll_interval);
+        Ok(())
+    }
+}
@@ -0,0 +1,183 @@
+use super::{ClipboardWatcher, PASSWORD_MANAGER_HINT};
+use crate::history::Selection;
+
+use anyhow::{Context, Result};
+use std::collections::HashSet;
+use std::time::Duration;
+use wayland_client::backend::ObjectId;
+use wayland_client::globals::{registry_queue_init, GlobalListContents};
+use wayland_client::protocol::{wl_registry, wl_seat::WlSeat};
+use wayland_client::{event_created_child, Connection, Dispatch, EventQueue, Proxy, QueueHandle};
+use wayland_protocols_wlr::data_control::v1::client::{
+    zwlr_data_control_device_v1::{self, ZwlrDataControlDeviceV1},
+    zwlr_data_control_manager_v1::ZwlrDataControlManagerV1,
+    zwlr_data_control_offer_v1::{self, ZwlrDataControlOfferV1},
+};
+
+/// Notified by the compositor through the wlr data-control protocol every
+/// time the clipboard selection, and optionally the primary one, changes.
+pub struct WaylandWatcher {
+    queue: EventQueue<State>,
+    state: State,
+    // Kept alive so the compositor keeps sending selection events
+    _device: ZwlrDataControlDeviceV1,
+}
+
+struct State {
+    changed: bool,
+    track_primary: bool,
+    /// Offers of the current selections, destroyed once they are replaced.
+    offer: Option<ZwlrDataControlOfferV1>,
+    primary_offer: Option<ZwlrDataControlOfferV1>,
+    /// Offers including the password manager hint in their formats.
+    sensitive_offers: HashSet<ObjectId>,
+}
+
+impl WaylandWatcher {
+    pub fn connect(track_primary: bool) -> Result<Self> {
+        let connection =
+            Connection::connect_to_env().context("Could not connect to the Wayland compositor.")?;
+        let (globals, queue) = registry_queue_init::<State>(&connection)
+            .context("Could not list the Wayland globals.")?;
+        let handle = queue.handle();
+
+        let seat: WlSeat = globals
+            .bind(&handle, 1..=1, ())
+            .context("The compositor has no seat.")?;
+        // The primary selection was added in version 2 of the protocol
+        let manager: ZwlrDataControlManagerV1 = globals
+            .bind(&handle, 1..=2, ())
+            .context("The compositor does not support the wlr data-control protocol.")?;
+        if track_primary && manager.version() < 2 {
+            tracing::warn!("The compositor does not report primary selection changes.");
+        }
+        let device = manager.get_data_device(&seat, &handle, ());
+
+        Ok(Self {
+            queue,
+            state: State {
+                changed: false,
+                track_primary,
+                offer: None,
+                primary_offer: None,
+                sensitive_offers: HashSet::new(),
+            },
+            _device: device,
+        })
+    }
+}
+
+impl ClipboardWatcher for WaylandWatcher {
+    fn name(&self) -> &'static str {
+        "Wayland"
+    }
+
+    fn wait_for_change(&mut self, _poll_interval: Duration) -> Result<()> {
+        while !self.state.changed {
+            self.queue
+                .blocking_dispatch(&mut self.state)
+                .context("Lost the connection to the Wayland compositor.")?;
+        }
+        self.state.changed = false;
+
+        Ok(())
+    }
+
+    fn is_sensitive(&mut self, selection: Selection) -> Result<bool> {
+        let offer = match selection {
+            Selection::Clipboard => &self.state.offer,
+            Selection::Primary => &self.state.primary_offer,
+        };
+
+        Ok(offer
+            .as_ref()
+            .is_some_and(|offer| self.state.sensitive_offers.contains(&offer.id())))
+    }
+}
+
+impl Dispatch<ZwlrDataControlDeviceV1, ()> for State {
+    fn event(
+        state: &mut Self,
+        _device: &ZwlrDataControlDeviceV1,
+        event: zwlr_data_control_device_v1::Event,
+        _data: &(),
+        _connection: &Connection,
+        _handle: &QueueHandle<Self>,
+    ) {
+        let (offer, id) = match event {
+            zwlr_data_control_device_v1::Event::Selection { id } => {
+                state.changed = true;
+                (&mut state.offer, id)
+            }
+            zwlr_data_control_device_v1::Event::PrimarySelection { id } => {
+                state.changed |= state.track_primary;
+                (&mut state.primary_offer, id)
+            }
+            _ => return,
+        };
+
+        if let Some(previous) = std::mem::replace(offer, id) {
+            state.sensitive_offers.remove(&previous.id());
+            previous.destroy();
+        }
+    }
+
+    event_created_child!(State, ZwlrDataControlDeviceV1, [
+        zwlr_data_control_device_v1::EVT_DATA_OFFER_OPCODE => (ZwlrDataControlOfferV1, ()),
+    ]);
+}
+
+// These objects send nothing we need
+impl Dispatch<wl_registry::WlRegistry, GlobalListContents> for State {
+    fn event(
+        _state: &mut Self,
+        _registry: &wl_registry::WlRegistry,
+        _event: wl_registry::Event,
+        _data: &GlobalListContents,
+        _connection: &Connection,
+        _handle: &QueueHandle<Self>,
+    ) {
+    }
+}
+
+impl Dispatch<WlSeat, ()> for State {
+    fn event(
+        _state: &mut Self,
+        _seat: &WlSeat,
+        _event: <WlSeat as Proxy>::Event,
+        _data: &(),
+        _connection: &Connection,
+        _handle: &QueueHandle<Self>,
+    ) {
+    }
+}
+
+impl Dispatch<ZwlrDataControlManagerV1, ()> for State {
+    fn event(
+        _state: &mut Self,
+        _manager: &ZwlrDataControlManagerV1,
+        _event: <ZwlrDataControlManagerV1 as Proxy>::Event,
+        _data: &(),
+        _connection: &Connection,
+        _handle: &QueueHandle<Self>,
+    ) {
+    }
+}
+
+// Offers list their formats before becoming a selection
+impl Dispatch<ZwlrDataControlOfferV1, ()> for State {
+    fn event(
+        state: &mut Self,
+        offer: &ZwlrDataControlOfferV1,
+        event: zwlr_data_control_offer_v1::Event,
+        _data: &(),
+        _connection: &Connection,
+        _handle: &QueueHandle<Self>,
+    ) {
+        if let zwlr_data_control_offer_v1::Event::Offer { mime_type } = event {
+            if mime_type == PASSWORD_MANAGER_HINT {
+                state.sensitive_offers.insert(offer.id());
+            }
+        }
+    }
+}
@@ -0,0 +1,188 @@
+use super::{ClipboardWatcher, PASSWORD_MANAGER_HINT};
+use crate::history::Selection;
+
+use anyhow::{anyhow, Context, Result};
+use std::thread;
+use std::time::{Duration, Instant};
+use x11rb::connection::Connection;
+use x11rb::protocol::xfixes::{ConnectionExt as _, SelectionEventMask};
+use x11rb::protocol::xproto::{
+    Atom, AtomEnum, ConnectionExt as _, CreateWindowAux, Window, WindowClass,
+};
+use x11rb::protocol::Event;
+use x11rb::rust_connection::RustConnection;
+use x11rb::{COPY_DEPTH_FROM_PARENT, COPY_FROM_PARENT, CURRENT_TIME, NONE};
+
+/// How long the owner of a selection has to list the formats it offers.
+const TARGETS_TIMEOUT: Duration = Duration::from_millis(500);
+
+/// Notified by the X server through the XFixes extension every time the
+/// owner of the clipboard selection, and optionally the primary one, changes.
+pub struct X11Watcher {
+    connection: RustConnection,
+    /// Hidden window the selection owners send their list of formats to.
+    window: Window,
+    atoms: Atoms,
+    /// A change was notified while waiting for a list of formats.
+    pending_change: bool,
+}
+
+struct Atoms {
+    clipboard: Atom,
+    targets: Atom,
+    password_manager_hint: Atom,
+    /// Property of our window the formats are written to.
+    property: Atom,
+}
+
+impl X11Watcher {
+    pub fn connect(track_primary: bool) -> Result<Self> {
+        let (connection, screen) =
+            x11rb::connect(None).context("Could not connect to the X server.")?;
+
+        connection
+            .xfixes_query_version(5, 0)?
+            .reply()
+            .context("The X server does not support the XFixes extension.")?;
+
+        let atom = |name: &str| -> Result<Atom> {
+            Ok(connection
+                .intern_atom(false, name.as_bytes())?
+                .reply()?
+                .atom)
+        };
+        let atoms = Atoms {
+            clipboard: atom("CLIPBOARD")?,
+            targets: atom("TARGETS")?,
+            password_manager_hint: atom(PASSWORD_MANAGER_HINT)?,
+            property: atom("CLIPPY_TARGETS")?,
+        };
+
+        let mut selections = vec![atoms.clipboard];
+        if track_primary {
+            selections.push(AtomEnum::PRIMARY.into());
+        }
+        let root = connection.setup().roots[screen].root;
+
+        for selection in selections {
+            connection
+                .xfixes_select_selection_input(
+                    root,
+                    selection,
+                    SelectionEventMask::SET_SELECTION_OWNER
+                        | SelectionEventMask::SELECTION_WINDOW_DESTROY
+                        | SelectionEventMask::SELECTION_CLIENT_CLOSE,
+                )?
+                .check()
+                .context("Could not watch the clipboard selection.")?;
+        }
+
+        let window = connection.generate_id()?;
+        connection
+            .create_window(
+                COPY_DEPTH_FROM_PARENT,
+                window,
+                root,
+                0,
+                0,
+                1,
+                1,
+                0,
+                WindowClass::INPUT_ONLY,
+                COPY_FROM_PARENT,
+                &CreateWindowAux::new(),
+            )?
+            .check()
+            .context("Could not create the selection requestor window.")?;
+
+        Ok(Self {
+            connection,
+            window,
+            atoms,
+            pending_change: false,
+        })
+    }
+}
+
+impl ClipboardWatcher for X11Watcher {
+    fn name(&self) -> &'static str {
+        "X11"
+    }
+
+    fn wait_for_change(&mut self, _poll_interval: Duration) -> Result<()> {
+        if std::mem::take(&mut self.pending_change) {
+            return Ok(());
+        }
+
+        loop {
+            let event = self
+                .connection
+                .wait_for_event()
+                .context("Lost the connection to the X server.")?;
+
+            if let Event::XfixesSelectionNotify(_) = event {
+                return Ok(());
+            }
+        }
+    }
+
+    /// Ask the owner of the selection for the formats it offers,
+    /// and look for the password manager hint among them.
+    fn is_sensitive(&mut self, selection: Selection) -> Result<bool> {
+        let selection = match selection {
+            Selection::Clipboard => self.atoms.clipboard,
+            Selection::Primary => AtomEnum::PRIMARY.into(),
+        };
+
+        self.connection.convert_selection(
+            self.window,
+            selection,
+            self.atoms.targets,
+            self.atoms.property,
+            CURRENT_TIME,
+        )?;
+        self.connection.flush()?;
+
+        let deadline = Instant::now() + TARGETS_TIMEOUT;
+        loop {
+            let event = self
+                .connection
+                .poll_for_event()
+                .context("Lost the connection to the X server.")?;
+
+            match event {
+                Some(Event::SelectionNotify(event)) if event.requestor == self.window => {
+                    // The owner could not list its formats
+                    if event.property == NONE {
+                        return Ok(false);
+                    }
+
+                    let targets = self
+                        .connection
+                        .get_property(
+                            true,
+                            self.window,
+                            self.atoms.property,
+                            AtomEnum::ATOM,
+                            0,
+                            1024,
+                        )?
+                        .reply()
+                        .context("Could not read the formats of the selection.")?;
+
+                    return Ok(targets.value32().is_some_and(|mut targets| {
+                        targets.any(|target| target == self.atoms.password_manager_hint)
+                    }));
+                }
+                Some(Event::XfixesSelectionNotify(_)) => self.pending_change = true,
+                Some(_) => {}
+                None if Instant::now() >= deadline => {
+                    return Err(anyhow!(
+                        "The selection owner did not list its formats in time."
+                    ))
+                }
+                None => thread::sleep(Duration::from_millis(5)),
+            }
+        }
+    }
+}
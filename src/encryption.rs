@@ -0,0 +1,257 @@
+//! Encryption of the history file at rest.
+//!
+//! An encrypted history file starts with `MAGIC`, followed by the salt the key
+//! was derived with when it comes from a passphrase, the nonce, and the RON
+//! history encrypted with XChaCha20-Poly1305.
+
+use anyhow::{anyhow, Context, Result};
+use argon2::Argon2;
+use chacha20poly1305::aead::rand_core::RngCore;
+use chacha20poly1305::aead::{Aead, AeadCore, KeyInit, OsRng};
+use chacha20poly1305::{XChaCha20Poly1305, XNonce};
+use clap::ValueEnum;
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::io::Write;
+use std::path::Path;
+use std::sync::Mutex;
+
+pub const KEY_LENGTH: usize = 32;
+const SALT_LENGTH: usize = 16;
+const NONCE_LENGTH: usize = 24;
+const MAGIC: &[u8] = b"CLIPPY-ENCRYPTED-1\n";
+
+/// Environment variable holding the passphrase when the key is derived from one.
+pub const PASSPHRASE_ENV_VAR: &str = "CLIPPY_PASSPHRASE";
+
+const KEYRING_SERVICE: &str = "clippy";
+const KEYRING_USER: &str = "history-key";
+
+/// Where the history key comes from.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize, ValueEnum)]
+#[serde(rename_all = "snake_case")]
+pub enum KeySource {
+    /// The history is saved in plain text.
+    #[default]
+    None,
+    /// A random key stored in a file only readable by its owner.
+    KeyFile,
+    /// A random key stored in the Secret Service, Keychain or Credential Manager.
+    Keyring,
+    /// A key derived from the passphrase in `CLIPPY_PASSPHRASE`.
+    Passphrase,
+}
+
+/// What the history key is made from.
+pub enum Secret {
+    Key([u8; KEY_LENGTH]),
+    Passphrase(String),
+}
+
+/// Encrypts and decrypts history files with a secret.
+pub struct Cipher {
+    secret: Secret,
+    /// Last key derived from the passphrase, along with its salt,
+    /// so saving the history does not derive a key every time.
+    derived: Mutex<Option<([u8; SALT_LENGTH], [u8; KEY_LENGTH])>>,
+}
+
+impl KeySource {
+    /// Get the secret of this source. Random keys are generated
+    /// and stored the first time. Returns `None` if encryption is disabled.
+    pub fn load(self, key_file: &Path) -> Result<Option<Secret>> {
+        let secret = match self {
+            KeySource::None => return Ok(None),
+            KeySource::KeyFile if key_file.exists() => Secret::Key(read_key_file(key_file)?),
+            KeySource::Keyring => match keyring_entry()?.get_password() {
+                Ok(key) => Secret::Key(decode_key(&key)?),
+                Err(keyring::Error::NoEntry) => self.create_key(key_file)?,
+                Err(e) => {
+                    return Err(e).context("Could not read the history key from the keyring.")
+                }
+            },
+            KeySource::KeyFile => self.create_key(key_file)?,
+            KeySource::Passphrase => Secret::Passphrase(
+                std::env::var(PASSPHRASE_ENV_VAR)
+                    .ok()
+                    .filter(|passphrase| !passphrase.is_empty())
+                    .ok_or_else(|| {
+                        anyhow!("{PASSPHRASE_ENV_VAR} must be set to decrypt the history.")
+                    })?,
+            ),
+        };
+
+        Ok(Some(secret))
+    }
+
+    /// Store a new secret, replacing the previous one. Passphrases
+    /// are not stored, they have to be given again on the next start.
+    pub fn store(self, key_file: &Path, secret: &Secret) -> Result<()> {
+        let (KeySource::KeyFile | KeySource::Keyring, Secret::Key(key)) = (self, secret) else {
+            return Ok(());
+        };
+
+        match self {
+            KeySource::KeyFile => write_key_file(key_file, key),
+            _ => keyring_entry()?
+                .set_password(&encode_key(key))
+                .context("Could not store the history key in the keyring."),
+        }
+    }
+
+    fn create_key(self, key_file: &Path) -> Result<Secret> {
+        tracing::info!("Generating a new history key ...");
+
+        let secret = Secret::Key(generate_key());
+        self.store(key_file, &secret)?;
+        Ok(secret)
+    }
+}
+
+impl Cipher {
+    pub fn new(secret: Secret) -> Self {
+        Self {
+            secret,
+            derived: Mutex::new(None),
+        }
+    }
+
+    pub fn secret(&self) -> &Secret {
+        &self.secret
+    }
+
+    pub fn encrypt(&self, plaintext: &[u8]) -> Result<Vec<u8>> {
+        let (salt, key) = match &self.secret {
+            Secret::Key(key) => ([0; SALT_LENGTH], *key),
+            Secret::Passphrase(_) => match self.cached_key() {
+                Some(derived) => derived,
+                None => {
+                    let mut salt = [0; SALT_LENGTH];
+                    OsRng.fill_bytes(&mut salt);
+                    (salt, self.key_for(&salt)?)
+                }
+            },
+        };
+
+        let nonce = XChaCha20Poly1305::generate_nonce(&mut OsRng);
+        let ciphertext = XChaCha20Poly1305::new(&key.into())
+            .encrypt(&nonce, plaintext)
+            .map_err(|_| anyhow!("Could not encrypt the history."))?;
+
+        Ok([MAGIC, &salt, &nonce, &ciphertext].concat())
+    }
+
+    pub fn decrypt(&self, data: &[u8]) -> Result<Vec<u8>> {
+        let header_length = MAGIC.len() + SALT_LENGTH + NONCE_LENGTH;
+        if !is_encrypted(data) || data.len() < header_length {
+            return Err(anyhow!(
+                "The history file is not encrypted or is truncated."
+            ));
+        }
+
+        let salt: [u8; SALT_LENGTH] = data[MAGIC.len()..MAGIC.len() + SALT_LENGTH].try_into()?;
+        let nonce = XNonce::from_slice(&data[MAGIC.len() + SALT_LENGTH..header_length]);
+        let key = self.key_for(&salt)?;
+
+        XChaCha20Poly1305::new(&key.into())
+            .decrypt(nonce, &data[header_length..])
+            .map_err(|_| anyhow!("Could not decrypt the history, the key is probably wrong."))
+    }
+
+    fn cached_key(&self) -> Option<([u8; SALT_LENGTH], [u8; KEY_LENGTH])> {
+        *self.derived.lock().ok()?
+    }
+
+    /// The key to use with a given salt, derived with Argon2 for passphrases.
+    fn key_for(&self, salt: &[u8; SALT_LENGTH]) -> Result<[u8; KEY_LENGTH]> {
+        let passphrase = match &self.secret {
+            Secret::Key(key) => return Ok(*key),
+            Secret::Passphrase(passphrase) => passphrase,
+        };
+
+        if let Some((cached_salt, key)) = self.cached_key() {
+            if cached_salt == *salt {
+                return Ok(key);
+            }
+        }
+
+        let mut key = [0; KEY_LENGTH];
+        Argon2::default()
+            .hash_password_into(passphrase.as_bytes(), salt, &mut key)
+            .map_err(|e| anyhow!("Could not derive the history key: {e}"))?;
+
+        if let Ok(mut derived) = self.derived.lock() {
+            *derived = Some((*salt, key));
+        }
+        Ok(key)
+    }
+}
+
+/// Whether a history file was written encrypted.
+pub fn is_encrypted(data: &[u8]) -> bool {
+    data.starts_with(MAGIC)
+}
+
+pub fn generate_key() -> [u8; KEY_LENGTH] {
+    let mut key = [0; KEY_LENGTH];
+    OsRng.fill_bytes(&mut key);
+    key
+}
+
+/// Read a key stored as hexadecimal text.
+pub fn read_key_file(path: &Path) -> Result<[u8; KEY_LENGTH]> {
+    let key = fs::read_to_string(path).context(format!(
+        "Could not read the key file \"{}\".",
+        path.display()
+    ))?;
+
+    decode_key(&key).context(format!("Invalid key file \"{}\".", path.display()))
+}
+
+/// Write a key as hexadecimal text, only readable by the current user.
+pub fn write_key_file(path: &Path, key: &[u8; KEY_LENGTH]) -> Result<()> {
+    if let Some(dir) = path.parent() {
+        fs::create_dir_all(dir).context(format!(
+            "Could not create the \"{}\" folder.",
+            dir.display()
+        ))?;
+    }
+
+    let mut options = fs::OpenOptions::new();
+    options.create(true).write(true).truncate(true);
+    #[cfg(unix)]
+    std::os::unix::fs::OpenOptionsExt::mode(&mut options, 0o600);
+
+    options
+        .open(path)
+        .and_then(|mut file| file.write_all(encode_key(key).as_bytes()))
+        .context(format!(
+            "Could not write the key file \"{}\".",
+            path.display()
+        ))
+}
+
+fn keyring_entry() -> Result<keyring::Entry> {
+    keyring::Entry::new(KEYRING_SERVICE, KEYRING_USER).context("Could not access the keyring.")
+}
+
+fn encode_key(key: &[u8; KEY_LENGTH]) -> String {
+    key.iter().map(|byte| format!("{byte:02x}")).collect()
+}
+
+fn decode_key(text: &str) -> Result<[u8; KEY_LENGTH]> {
+    let text = text.trim();
+    if text.len() != KEY_LENGTH * 2 || !text.is_ascii() {
+        return Err(anyhow!(
+            "The key must be {} hexadecimal characters.",
+            KEY_LENGTH * 2
+        ));
+    }
+
+    let mut key = [0; KEY_LENGTH];
+    for (index, byte) in key.iter_mut().enumerate() {
+        *byte = u8::from_str_radix(&text[index * 2..index * 2 + 2], 16)
+            .context("The key is not valid hexadecimal.")?;
+    }
+    Ok(key)
+}
@@ -0,0 +1,366 @@
+use anyhow::{Context, Result};
+use arboard::ImageData;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::collections::HashMap;
+use std::fs;
+use std::path::PathBuf;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// The clipboard history, most recent entry first, along with the
+/// sequence number of the last change applied to it.
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct History {
+    pub seq: u64,
+    pub entries: Vec<HistoryEntry>,
+}
+
+/// A change of the history, numbered so that clients following
+/// the changes can tell when they missed one.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct HistoryDelta {
+    pub seq: u64,
+    pub change: HistoryChange,
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub enum HistoryChange {
+    Insert {
+        index: usize,
+        entry: HistoryEntry,
+    },
+    Remove {
+        id: u64,
+    },
+    MoveToTop {
+        id: u64,
+    },
+    /// Replace the entry having the same id, for example when it was pinned.
+    Update(HistoryEntry),
+    /// Remove every unpinned entry.
+    Clear,
+}
+
+/// An entry of the clipboard history, with the copied
+/// value and some metadata about it.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct HistoryEntry {
+    pub id: u64,
+    pub content: ClipboardContent,
+    /// Unix timestamps, in seconds.
+    pub first_copied: u64,
+    pub last_copied: u64,
+    pub copy_count: u32,
+    pub kind: ContentKind,
+    pub byte_size: usize,
+    /// Pinned entries are kept when the history is full or cleared.
+    #[serde(default)]
+    pub pinned: bool,
+    /// Selection the value was last copied from.
+    #[serde(default)]
+    pub source: Selection,
+    /// Sensitive entries expire sooner than the others.
+    #[serde(default)]
+    pub sensitive: bool,
+}
+
+/// A single value captured from the clipboard.
+///
+/// The enum is untagged so that a plain string deserializes as `Text`,
+/// which keeps history files written before images were supported readable.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(untagged)]
+pub enum ClipboardContent {
+    Text(String),
+    Image(ImageEntry),
+}
+
+/// An image copied to the clipboard. The pixels are not kept in the history
+/// itself, they are stored as a PNG file next to the history file and
+/// identified by the hash of their content.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct ImageEntry {
+    pub hash: String,
+    pub width: usize,
+    pub height: usize,
+    pub path: PathBuf,
+}
+
+/// What a copied value looks like, guessed when it enters the history.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub enum ContentKind {
+    Text,
+    Url,
+    Path,
+    Image,
+}
+
+/// A system selection holding a copied value. The primary selection is filled
+/// by selecting text with the mouse and only exists on Linux, elsewhere it
+/// falls back to the clipboard.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum Selection {
+    #[default]
+    Clipboard,
+    Primary,
+}
+
+impl History {
+    pub fn new(entries: Vec<HistoryEntry>) -> Self {
+        Self { seq: 0, entries }
+    }
+
+    /// Apply a new change and return the delta describing it.
+    pub fn commit(&mut self, change: HistoryChange) -> HistoryDelta {
+        change.apply(&mut self.entries);
+        self.seq += 1;
+
+        HistoryDelta {
+            seq: self.seq,
+            change,
+        }
+    }
+
+    /// Apply a delta received from the daemon. Deltas that are already part
+    /// of the history are ignored. Returns false if some deltas were missed,
+    /// in which case the whole history has to be fetched again.
+    pub fn apply(&mut self, delta: &HistoryDelta) -> bool {
+        if delta.seq <= self.seq {
+            return true;
+        }
+        if delta.seq != self.seq + 1 {
+            return false;
+        }
+
+        delta.change.apply(&mut self.entries);
+        self.seq = delta.seq;
+        true
+    }
+}
+
+impl HistoryChange {
+    fn apply(&self, entries: &mut Vec<HistoryEntry>) {
+        match self {
+            HistoryChange::Insert { index, entry } => {
+                entries.insert((*index).min(entries.len()), entry.clone())
+            }
+            HistoryChange::Remove { id } => entries.retain(|entry| entry.id != *id),
+            HistoryChange::MoveToTop { id } => {
+                if let Some(position) = entries.iter().position(|entry| entry.id == *id) {
+                    let entry = entries.remove(position);
+                    entries.insert(0, entry);
+                }
+            }
+            HistoryChange::Update(updated) => {
+                if let Some(entry) = entries.iter_mut().find(|entry| entry.id == updated.id) {
+                    *entry = updated.clone();
+                }
+            }
+            HistoryChange::Clear => entries.retain(|entry| entry.pinned),
+        }
+    }
+}
+
+/// SHA-256 digest of a copied value.
+pub type ContentDigest = [u8; 32];
+
+/// Entry ids by the digest of their value, so a copied value is matched
+/// with its entry without comparing it to the whole history.
+#[derive(Clone, Debug, Default)]
+pub struct ContentIndex {
+    ignore_whitespace: bool,
+    ids: HashMap<ContentDigest, Vec<u64>>,
+    digests: HashMap<u64, ContentDigest>,
+}
+
+impl ContentIndex {
+    pub fn new(entries: &[HistoryEntry], ignore_whitespace: bool) -> Self {
+        let mut index = Self {
+            ignore_whitespace,
+            ..Default::default()
+        };
+        for entry in entries {
+            index.insert(entry);
+        }
+        index
+    }
+
+    /// Whether values differing only by whitespace share their digest.
+    pub fn ignores_whitespace(&self) -> bool {
+        self.ignore_whitespace
+    }
+
+    /// Id of an entry whose value may be a duplicate of `content`. Digests
+    /// can collide, so the caller still has to compare the values.
+    pub fn find(&self, content: &ClipboardContent) -> Option<u64> {
+        self.ids
+            .get(&content.digest(self.ignore_whitespace))?
+            .last()
+            .copied()
+    }
+
+    /// Follow a change about to be applied to `entries`.
+    pub fn apply(&mut self, entries: &[HistoryEntry], change: &HistoryChange) {
+        match change {
+            HistoryChange::Insert { entry, .. } => self.insert(entry),
+            HistoryChange::Remove { id } => self.remove(*id),
+            HistoryChange::MoveToTop { .. } => {}
+            HistoryChange::Update(entry) => {
+                self.remove(entry.id);
+                self.insert(entry);
+            }
+            HistoryChange::Clear => {
+                for entry in entries.iter().filter(|entry| !entry.pinned) {
+                    self.remove(entry.id);
+                }
+            }
+        }
+    }
+
+    fn insert(&mut self, entry: &HistoryEntry) {
+        let digest = entry.content.digest(self.ignore_whitespace);
+        self.ids.entry(digest).or_default().push(entry.id);
+        self.digests.insert(entry.id, digest);
+    }
+
+    fn remove(&mut self, id: u64) {
+        let Some(digest) = self.digests.remove(&id) else {
+            return;
+        };
+        if let Some(ids) = self.ids.get_mut(&digest) {
+            ids.retain(|&other| other != id);
+            if ids.is_empty() {
+                self.ids.remove(&digest);
+            }
+        }
+    }
+}
+
+impl HistoryEntry {
+    /// Create an entry for a value copied right now.
+    pub fn new(id: u64, content: ClipboardContent, source: Selection) -> Self {
+        let now = now();
+
+        Self {
+            id,
+            kind: ContentKind::of(&content),
+            byte_size: content.byte_size(),
+            content,
+            first_copied: now,
+            last_copied: now,
+            copy_count: 1,
+            pinned: false,
+            source,
+            sensitive: false,
+        }
+    }
+
+    /// Convert a history saved as a plain list of values, most recent first.
+    /// Copy times were not recorded back then, so they are set to now.
+    pub fn migrate(legacy_history: Vec<ClipboardContent>) -> Vec<Self> {
+        let len = legacy_history.len() as u64;
+
+        legacy_history
+            .into_iter()
+            .enumerate()
+            .map(|(index, content)| {
+                Self::new(len - index as u64 - 1, content, Selection::Clipboard)
+            })
+            .collect()
+    }
+}
+
+impl ClipboardContent {
+    /// Whether two values belong to the same entry, optionally
+    /// ignoring the whitespace differences between texts.
+    pub fn is_duplicate_of(&self, other: &ClipboardContent, ignore_whitespace: bool) -> bool {
+        match (self, other) {
+            (ClipboardContent::Text(text), ClipboardContent::Text(other)) if ignore_whitespace => {
+                text.split_whitespace().eq(other.split_whitespace())
+            }
+            _ => self == other,
+        }
+    }
+
+    /// Digest of the value, the same for values that are duplicates of each other.
+    pub fn digest(&self, ignore_whitespace: bool) -> ContentDigest {
+        let mut hasher = Sha256::new();
+        match self {
+            ClipboardContent::Text(text) if ignore_whitespace => {
+                hasher.update(b"t");
+                for word in text.split_whitespace() {
+                    hasher.update(word.as_bytes());
+                    hasher.update(b" ");
+                }
+            }
+            ClipboardContent::Text(text) => {
+                hasher.update(b"t");
+                hasher.update(text.as_bytes());
+            }
+            ClipboardContent::Image(image) => {
+                hasher.update(b"i");
+                hasher.update(image.hash.as_bytes());
+            }
+        }
+        hasher.finalize().into()
+    }
+
+    /// Size of the value itself, decoded pixels for images.
+    pub fn byte_size(&self) -> usize {
+        match self {
+            ClipboardContent::Text(text) => text.len(),
+            ClipboardContent::Image(image) => image.width * image.height * 4,
+        }
+    }
+}
+
+impl ImageEntry {
+    /// Decode the PNG file the daemon stored for this image.
+    pub fn load(&self) -> Result<ImageData<'static>> {
+        let file = fs::File::open(&self.path)
+            .context(format!("Could not open \"{}\"", self.path.display()))?;
+
+        let mut reader = png::Decoder::new(file)
+            .read_info()
+            .context(format!("Could not read \"{}\"", self.path.display()))?;
+
+        let mut bytes = vec![0; reader.output_buffer_size()];
+        let frame = reader
+            .next_frame(&mut bytes)
+            .context(format!("Could not decode \"{}\"", self.path.display()))?;
+        bytes.truncate(frame.buffer_size());
+
+        Ok(ImageData {
+            width: frame.width as usize,
+            height: frame.height as usize,
+            bytes: bytes.into(),
+        })
+    }
+}
+
+impl ContentKind {
+    pub fn of(content: &ClipboardContent) -> Self {
+        let text = match content {
+            ClipboardContent::Text(text) => text.trim(),
+            ClipboardContent::Image(_) => return ContentKind::Image,
+        };
+
+        if text.contains(char::is_whitespace) {
+            ContentKind::Text
+        } else if text.starts_with("http://") || text.starts_with("https://") {
+            ContentKind::Url
+        } else if text.starts_with('/') || text.starts_with("~/") {
+            ContentKind::Path
+        } else {
+            ContentKind::Text
+        }
+    }
+}
+
+/// Current time as a unix timestamp, in seconds.
+pub fn now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|duration| duration.as_secs())
+        .unwrap_or_default()
+}
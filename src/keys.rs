@@ -0,0 +1,104 @@
+//! Key combinations shared by the global hotkey of the daemon
+//! and the paste keystroke of the UI.
+
+use anyhow::{anyhow, Result};
+use std::str::FromStr;
+
+/// A key pressed with modifiers, such as `Ctrl+Alt+V`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Hotkey {
+    pub ctrl: bool,
+    pub shift: bool,
+    pub alt: bool,
+    pub super_key: bool,
+    /// X11 keysym of the key.
+    pub keysym: u32,
+}
+
+impl FromStr for Hotkey {
+    type Err = anyhow::Error;
+
+    /// Parse key names joined to their modifiers with `+`. A modifier is
+    /// required, so the key keeps working in the other applications.
+    fn from_str(text: &str) -> Result<Self> {
+        let (modifiers, key) = text
+            .rsplit_once('+')
+            .ok_or_else(|| anyhow!("The hotkey \"{text}\" needs at least one modifier."))?;
+
+        let mut hotkey = Hotkey {
+            ctrl: false,
+            shift: false,
+            alt: false,
+            super_key: false,
+            keysym: keysym(key.trim())
+                .ok_or_else(|| anyhow!("Unknown key \"{key}\" in the hotkey \"{text}\"."))?,
+        };
+
+        for modifier in modifiers.split('+').map(str::trim) {
+            match modifier.to_lowercase().as_str() {
+                "ctrl" | "control" => hotkey.ctrl = true,
+                "shift" => hotkey.shift = true,
+                "alt" => hotkey.alt = true,
+                "super" | "win" | "meta" => hotkey.super_key = true,
+                _ => {
+                    return Err(anyhow!(
+                        "Unknown modifier \"{modifier}\" in the hotkey \"{text}\"."
+                    ))
+                }
+            }
+        }
+
+        Ok(hotkey)
+    }
+}
+
+/// X11 keysym of a letter, a digit, a function key or a few named keys.
+fn keysym(key: &str) -> Option<u32> {
+    let mut chars = key.chars();
+    if let (Some(c), None) = (chars.next(), chars.next()) {
+        return c
+            .is_ascii_alphanumeric()
+            .then(|| c.to_ascii_lowercase() as u32);
+    }
+
+    if let Some(number) = key.strip_prefix(['F', 'f']) {
+        return match number.parse::<u32>() {
+            Ok(number @ 1..=12) => Some(0xffbe + number - 1),
+            _ => None,
+        };
+    }
+
+    match key.to_lowercase().as_str() {
+        "space" => Some(0x0020),
+        "enter" | "return" => Some(0xff0d),
+        "escape" | "esc" => Some(0xff1b),
+        "tab" => Some(0xff09),
+        "insert" => Some(0xff63),
+        "home" => Some(0xff50),
+        "end" => Some(0xff57),
+        _ => None,
+    }
+}
+
+/// Keycode of the key producing a keysym on the current keyboard layout.
+#[cfg(target_os = "linux")]
+pub(crate) fn keycode(
+    connection: &impl x11rb::connection::Connection,
+    keysym: u32,
+) -> Result<x11rb::protocol::xproto::Keycode> {
+    use anyhow::Context;
+    use x11rb::protocol::xproto::ConnectionExt as _;
+
+    let setup = connection.setup();
+    let mapping = connection
+        .get_keyboard_mapping(setup.min_keycode, setup.max_keycode - setup.min_keycode + 1)?
+        .reply()
+        .context("Could not read the keyboard mapping.")?;
+
+    mapping
+        .keysyms
+        .chunks(usize::from(mapping.keysyms_per_keycode).max(1))
+        .position(|keysyms| keysyms.contains(&keysym))
+        .map(|index| setup.min_keycode + index as u8)
+        .ok_or_else(|| anyhow!("No key of the keyboard produces the keysym {keysym:#x}."))
+}
@@ -0,0 +1,16 @@
+//! Clipboard history shared by the clippy daemon, its UI and `clippyctl`.
+//!
+//! Other tools can talk to a running daemon with `client::DaemonClient`,
+//! or embed the daemon itself with `daemon::Clippy`.
+
+pub mod client;
+pub mod clipboard;
+pub mod daemon;
+pub mod encryption;
+pub mod history;
+pub mod keys;
+pub mod paste;
+pub mod protocol;
+pub mod search;
+pub mod storage;
+pub mod transport;
@@ -0,0 +1,109 @@
+//! Pasting into the window that had the focus before the UI.
+//!
+//! Once an entry is copied, the UI hides and an injector gives the focus back
+//! to the window that was active when the UI was shown, then presses the
+//! paste keystroke in it. On X11 the keystroke is synthesized with the XTest
+//! extension, elsewhere nothing is pasted.
+
+#[cfg(target_os = "linux")]
+mod x11;
+
+#[cfg(target_os = "linux")]
+pub use x11::XTestInjector;
+
+use crate::clipboard::ClipboardProvider;
+use crate::history::{ClipboardContent, Selection};
+use crate::keys::Hotkey;
+
+use anyhow::{anyhow, Result};
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::Duration;
+
+pub trait PasteInjector: Send {
+    /// Remember the window that has the focus, before the UI takes it.
+    /// The window remembered last is kept while the UI has the focus.
+    fn remember_focus(&mut self) -> Result<()>;
+
+    /// Give the focus back to the remembered window, if any,
+    /// and press the paste keystroke.
+    fn paste(&mut self, keystroke: &Hotkey) -> Result<()>;
+}
+
+/// Injector doing nothing, for display servers where keystrokes can not be
+/// synthesized. It keeps the keystrokes it was asked to press, for tests.
+#[derive(Clone, Debug, Default)]
+pub struct NoopInjector {
+    pastes: Arc<Mutex<Vec<Hotkey>>>,
+}
+
+impl NoopInjector {
+    /// The keystrokes pressed so far, shared by the clones of the injector.
+    pub fn pastes(&self) -> Vec<Hotkey> {
+        self.pastes
+            .lock()
+            .map(|pastes| pastes.clone())
+            .unwrap_or_default()
+    }
+}
+
+impl PasteInjector for NoopInjector {
+    fn remember_focus(&mut self) -> Result<()> {
+        Ok(())
+    }
+
+    fn paste(&mut self, keystroke: &Hotkey) -> Result<()> {
+        self.pastes
+            .lock()
+            .map_err(|e| anyhow!("Could not acquire pastes lock: {}", e))?
+            .push(*keystroke);
+        Ok(())
+    }
+}
+
+/// The injector of the display server, a no-op one when it has none.
+pub fn default_injector() -> Box<dyn PasteInjector> {
+    // Keystrokes sent through XWayland would only reach X11 applications
+    #[cfg(target_os = "linux")]
+    if std::env::var_os("WAYLAND_DISPLAY").is_none() {
+        match XTestInjector::connect() {
+            Ok(injector) => return Box::new(injector),
+            Err(e) => tracing::warn!("Paste on select is not available: {e:#}"),
+        }
+    }
+
+    Box::new(NoopInjector::default())
+}
+
+/// Put the value of the selected entry into the clipboard, then paste it once
+/// `delay` elapsed. Nothing is pasted when the value could not be copied.
+pub fn copy_and_paste(
+    clipboard: &mut dyn ClipboardProvider,
+    content: &ClipboardContent,
+    injector: Arc<Mutex<Box<dyn PasteInjector>>>,
+    keystroke: Hotkey,
+    delay: Duration,
+) -> Result<thread::JoinHandle<()>> {
+    clipboard.set_content(Selection::Clipboard, content)?;
+    Ok(paste_later(injector, keystroke, delay))
+}
+
+/// Paste in a background thread once `delay` elapsed,
+/// leaving the UI the time to hide.
+pub fn paste_later(
+    injector: Arc<Mutex<Box<dyn PasteInjector>>>,
+    keystroke: Hotkey,
+    delay: Duration,
+) -> thread::JoinHandle<()> {
+    thread::spawn(move || {
+        thread::sleep(delay);
+
+        let result = injector
+            .lock()
+            .map_err(|e| anyhow!("Could not acquire injector lock: {}", e))
+            .and_then(|mut injector| injector.paste(&keystroke));
+        if let Err(e) = result {
+            tracing::error!("Could not paste the copied entry: {e:#}");
+        }
+    })
+}
@@ -0,0 +1,164 @@
+use super::PasteInjector;
+use crate::keys::{self, Hotkey};
+
+use anyhow::{Context, Result};
+use std::thread;
+use std::time::Duration;
+use x11rb::connection::Connection;
+use x11rb::protocol::xproto::{
+    Atom, AtomEnum, ClientMessageEvent, ConnectionExt as _, EventMask, Window, KEY_PRESS_EVENT,
+    KEY_RELEASE_EVENT,
+};
+use x11rb::protocol::xtest::ConnectionExt as _;
+use x11rb::rust_connection::RustConnection;
+use x11rb::{CURRENT_TIME, NONE};
+
+/// Time the window manager has to focus the window we paste into.
+const FOCUS_DELAY: Duration = Duration::from_millis(50);
+
+const CONTROL_L: u32 = 0xffe3;
+const SHIFT_L: u32 = 0xffe1;
+const ALT_L: u32 = 0xffe9;
+const SUPER_L: u32 = 0xffeb;
+
+/// Focuses windows through the EWMH `_NET_ACTIVE_WINDOW` message
+/// and presses keys with the XTest extension.
+pub struct XTestInjector {
+    connection: RustConnection,
+    root: Window,
+    active_window: Atom,
+    wm_pid: Atom,
+    previous_window: Option<Window>,
+}
+
+impl XTestInjector {
+    pub fn connect() -> Result<Self> {
+        let (connection, screen) =
+            x11rb::connect(None).context("Could not connect to the X server.")?;
+
+        connection
+            .xtest_get_version(2, 2)?
+            .reply()
+            .context("The X server does not support the XTest extension.")?;
+
+        let root = connection.setup().roots[screen].root;
+        let active_window = connection
+            .intern_atom(false, b"_NET_ACTIVE_WINDOW")?
+            .reply()?
+            .atom;
+        let wm_pid = connection.intern_atom(false, b"_NET_WM_PID")?.reply()?.atom;
+
+        Ok(Self {
+            connection,
+            root,
+            active_window,
+            wm_pid,
+            previous_window: None,
+        })
+    }
+
+    /// Whether a window belongs to this process, as told by its `_NET_WM_PID`.
+    fn is_own_window(&self, window: Window) -> Result<bool> {
+        let reply = self
+            .connection
+            .get_property(false, window, self.wm_pid, AtomEnum::CARDINAL, 0, 1)?
+            .reply()
+            .context("Could not read the process of the active window.")?;
+
+        Ok(reply
+            .value32()
+            .and_then(|mut pids| pids.next())
+            .is_some_and(|pid| pid == std::process::id()))
+    }
+
+    /// Ask the window manager to focus a window.
+    fn focus(&self, window: Window) -> Result<()> {
+        // Source 2 tells the request comes from a pager, which window managers obey
+        let event = ClientMessageEvent::new(32, window, self.active_window, [2, 0, 0, 0, 0]);
+        self.connection
+            .send_event(
+                false,
+                self.root,
+                EventMask::SUBSTRUCTURE_REDIRECT | EventMask::SUBSTRUCTURE_NOTIFY,
+                event,
+            )?
+            .check()
+            .context("Could not focus the previous window.")?;
+
+        Ok(())
+    }
+
+    fn press(&self, keycode: u8, pressed: bool) -> Result<()> {
+        let event_type = if pressed {
+            KEY_PRESS_EVENT
+        } else {
+            KEY_RELEASE_EVENT
+        };
+
+        self.connection
+            .xtest_fake_input(event_type, keycode, CURRENT_TIME, self.root, 0, 0, 0)?
+            .check()
+            .context("Could not synthesize a key event.")?;
+
+        Ok(())
+    }
+}
+
+impl PasteInjector for XTestInjector {
+    fn remember_focus(&mut self) -> Result<()> {
+        let reply = self
+            .connection
+            .get_property(false, self.root, self.active_window, AtomEnum::WINDOW, 0, 1)?
+            .reply()
+            .context("Could not read the active window.")?;
+
+        let active = reply
+            .value32()
+            .and_then(|mut windows| windows.next())
+            .filter(|&window| window != NONE);
+
+        // Once the UI has the focus, the window it was shown over is kept
+        match active {
+            Some(window) if self.is_own_window(window)? => {}
+            window => self.previous_window = window,
+        }
+        Ok(())
+    }
+
+    fn paste(&mut self, keystroke: &Hotkey) -> Result<()> {
+        if let Some(window) = self.previous_window.take() {
+            self.focus(window)?;
+            thread::sleep(FOCUS_DELAY);
+        }
+
+        let modifiers = [
+            (keystroke.ctrl, CONTROL_L),
+            (keystroke.shift, SHIFT_L),
+            (keystroke.alt, ALT_L),
+            (keystroke.super_key, SUPER_L),
+        ]
+        .into_iter()
+        .filter(|(held, _)| *held)
+        .map(|(_, keysym)| keys::keycode(&self.connection, keysym))
+        .collect::<Result<Vec<_>>>()?;
+        let key = keys::keycode(&self.connection, keystroke.keysym)?;
+
+        let mut pressed = Vec::new();
+        let pressing = modifiers.iter().chain([&key]).try_for_each(|&keycode| {
+            // Released even if the press fails, it may still have reached the server
+            pressed.push(keycode);
+            self.press(keycode, true)
+        });
+
+        // A key left pressed would stay held for every application
+        let mut releasing = Ok(());
+        for &keycode in pressed.iter().rev() {
+            if let Err(e) = self.press(keycode, false) {
+                releasing = releasing.and(Err(e));
+            }
+        }
+        let flushing = self.connection.flush().map_err(anyhow::Error::from);
+
+        pressing.and(releasing).and(flushing)
+    }
+}
@@ -0,0 +1,192 @@
+//! Messages exchanged between the daemon and its clients.
+//!
+//! Every message is a frame made of its length as a big endian `u32`
+//! followed by the message serialized with RON. A connection starts with a
+//! handshake: the client sends `Request::Hello` with its protocol version and
+//! the daemon answers `Response::Hello`, or an `UnsupportedVersion` error.
+//!
+//! After a `Request::Subscribe`, the daemon answers with the current history
+//! and then pushes a `Response::Delta` on the connection for every change.
+//! A subscriber noticing a gap in the sequence numbers can send
+//! `Request::GetHistory` on the same connection to resync. Subscribers also
+//! receive `Response::ShowUi` when the user summons the UI.
+
+use crate::history::{History, HistoryDelta, Selection};
+use crate::search::{SearchMode, SearchResult};
+
+use anyhow::{anyhow, Context, Result};
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use std::io::{ErrorKind, Read, Write};
+
+pub const PROTOCOL_VERSION: u32 = 3;
+
+/// Upper bound on a frame length, so a corrupted length prefix
+/// does not make us allocate gigabytes.
+const MAX_MESSAGE_LENGTH: u32 = 64 * 1024 * 1024;
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub enum Request {
+    Hello {
+        version: u32,
+    },
+    GetHistory,
+    ClearHistory,
+    SetPinned {
+        id: u64,
+        pinned: bool,
+    },
+    /// Flag an entry as sensitive, so it expires sooner.
+    SetSensitive {
+        id: u64,
+        sensitive: bool,
+    },
+    /// Put an entry back into the clipboard, or the primary selection.
+    Copy {
+        id: u64,
+        #[serde(default)]
+        selection: Selection,
+    },
+    Delete {
+        id: u64,
+    },
+    /// Remove several entries at once. Ids that are not in the history are ignored.
+    DeleteMany {
+        ids: Vec<u64>,
+    },
+    /// Record a text copied while the daemon was not watching, for example
+    /// by `wl-paste --watch`. It goes through the same filter as the clipboard.
+    Store {
+        text: String,
+    },
+    Subscribe,
+    /// Find the entries matching a query, best first.
+    Search {
+        query: String,
+        #[serde(default)]
+        mode: SearchMode,
+        /// Only return the first results.
+        #[serde(default)]
+        limit: Option<usize>,
+    },
+    /// Ask the running UIs to show themselves at the mouse cursor.
+    ShowUi,
+    /// Encrypt the history file with a new key. The new passphrase
+    /// is required when the key is derived from one.
+    Rekey {
+        passphrase: Option<String>,
+    },
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub enum Response {
+    Hello {
+        version: u32,
+    },
+    History(History),
+    Ok,
+    Error {
+        code: ErrorCode,
+        message: String,
+    },
+    Delta(HistoryDelta),
+    SearchResults(Vec<SearchResult>),
+    /// Pushed to the subscribers when the UI is summoned, with the
+    /// position of the mouse cursor on the screen when it is known.
+    ShowUi {
+        position: Option<(i32, i32)>,
+    },
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub enum ErrorCode {
+    /// The message could not be parsed or is not valid at this point.
+    BadRequest,
+    /// The first message of the connection was not a handshake.
+    HandshakeRequired,
+    UnsupportedVersion,
+    /// The request targets an entry that is not in the history,
+    /// or a UI when none is running.
+    NotFound,
+    /// The daemon failed to handle a valid request.
+    Internal,
+}
+
+/// An error response received from the daemon.
+#[derive(Debug)]
+pub struct DaemonError {
+    pub code: ErrorCode,
+    pub message: String,
+}
+
+impl fmt::Display for DaemonError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "daemon error {:?}: {}", self.code, self.message)
+    }
+}
+
+impl std::error::Error for DaemonError {}
+
+impl Response {
+    pub fn error(code: ErrorCode, message: impl Into<String>) -> Self {
+        Response::Error {
+            code,
+            message: message.into(),
+        }
+    }
+
+    /// Turn an error response into an `Err`.
+    pub fn into_result(self) -> Result<Self> {
+        match self {
+            Response::Error { code, message } => Err(DaemonError { code, message }.into()),
+            response => Ok(response),
+        }
+    }
+}
+
+/// Serialize a message and write it as a single frame.
+pub fn write_message<T: Serialize>(writer: &mut impl Write, message: &T) -> Result<()> {
+    let payload = ron::ser::to_string(message).context("Could not serialize message.")?;
+
+    let length = u32::try_from(payload.len())
+        .ok()
+        .filter(|length| *length <= MAX_MESSAGE_LENGTH)
+        .ok_or_else(|| {
+            anyhow!(
+                "Message of {} bytes is too large to be sent.",
+                payload.len()
+            )
+        })?;
+
+    writer
+        .write_all(&length.to_be_bytes())
+        .and_then(|()| writer.write_all(payload.as_bytes()))
+        .and_then(|()| writer.flush())
+        .context("Could not write message to stream.")
+}
+
+/// Read a single frame and deserialize the message it holds.
+/// Returns `None` if the stream was closed before a new frame started.
+pub fn read_message<T: DeserializeOwned>(reader: &mut impl Read) -> Result<Option<T>> {
+    let mut length = [0; 4];
+    match reader.read_exact(&mut length) {
+        Ok(()) => {}
+        Err(e) if e.kind() == ErrorKind::UnexpectedEof => return Ok(None),
+        Err(e) => return Err(e).context("Could not read message length from stream."),
+    }
+
+    let length = u32::from_be_bytes(length);
+    if length > MAX_MESSAGE_LENGTH {
+        return Err(anyhow!("Incoming message of {length} bytes is too large."));
+    }
+
+    let mut payload = vec![0; length as usize];
+    reader
+        .read_exact(&mut payload)
+        .context("Could not read message from stream.")?;
+
+    ron::de::from_bytes(&payload)
+        .map(Some)
+        .context("Could not deserialize message.")
+}
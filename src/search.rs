@@ -0,0 +1,184 @@
+//! Searching the history.
+//!
+//! A query matches text entries ignoring case, as a fuzzy pattern in the
+//! style of fzf, or as a regular expression. Results are ranked best first,
+//! and carry the byte ranges of the text that matched so they can be
+//! highlighted.
+
+use crate::history::{ClipboardContent, HistoryEntry};
+
+use anyhow::{Context, Result};
+use clap::ValueEnum;
+use fuzzy_matcher::skim::SkimMatcherV2;
+use fuzzy_matcher::FuzzyMatcher;
+use regex::{Regex, RegexBuilder};
+use serde::{Deserialize, Serialize};
+use std::ops::Range;
+
+/// How a query is matched against the entries.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize, ValueEnum)]
+#[serde(rename_all = "lowercase")]
+pub enum SearchMode {
+    /// The text contains the query, ignoring case.
+    #[default]
+    Text,
+    /// The characters of the query appear in order in the text.
+    Fuzzy,
+    /// The text matches the query as a regular expression.
+    Regex,
+}
+
+/// An entry matching a query.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct SearchResult {
+    /// Position of the entry in the history.
+    pub index: usize,
+    pub entry: HistoryEntry,
+    pub score: i64,
+    /// Byte ranges of the matched parts of the text.
+    pub ranges: Vec<Range<usize>>,
+}
+
+/// Where a query matched a text.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Match {
+    pub score: i64,
+    pub ranges: Vec<Range<usize>>,
+}
+
+/// A query, ready to be matched against many texts.
+#[derive(Clone, Debug)]
+pub struct Query {
+    text: String,
+    mode: SearchMode,
+    /// Used by the text and regex modes.
+    regex: Option<Regex>,
+}
+
+impl Query {
+    /// Fails if the query is not a valid regular expression in regex mode.
+    pub fn new(text: &str, mode: SearchMode) -> Result<Self> {
+        let pattern = match mode {
+            SearchMode::Text => Some(regex::escape(text.trim())),
+            SearchMode::Regex => Some(text.to_string()),
+            SearchMode::Fuzzy => None,
+        };
+        let regex = pattern
+            .map(|pattern| {
+                RegexBuilder::new(&pattern)
+                    .case_insensitive(mode == SearchMode::Text)
+                    .build()
+                    .context(format!("Invalid regular expression \"{text}\"."))
+            })
+            .transpose()?;
+
+        Ok(Self {
+            text: text.trim().to_string(),
+            mode,
+            regex,
+        })
+    }
+
+    /// An empty query matches every entry.
+    pub fn is_empty(&self) -> bool {
+        self.text.is_empty()
+    }
+
+    /// Where the query matches a text, `None` if it does not.
+    pub fn find(&self, text: &str) -> Option<Match> {
+        if self.is_empty() {
+            return Some(Match {
+                score: 0,
+                ranges: Vec::new(),
+            });
+        }
+
+        match self.mode {
+            SearchMode::Fuzzy => self.find_fuzzy(text),
+            SearchMode::Text => {
+                let regex = self.regex.as_ref()?;
+                let first = regex.find(text)?;
+                let ranges: Vec<Range<usize>> = regex.find_iter(text).map(|m| m.range()).collect();
+
+                // Matching the whole value, then its start or
+                // the start of a word, ranks first
+                let value_start = text.len() - text.trim_start().len();
+                let score = if first.start() == value_start && first.len() == text.trim().len() {
+                    100
+                } else if first.start() == value_start {
+                    50
+                } else if text[..first.start()].ends_with(|c: char| !c.is_alphanumeric()) {
+                    25
+                } else {
+                    10
+                };
+
+                Some(Match {
+                    score: score + ranges.len() as i64,
+                    ranges,
+                })
+            }
+            SearchMode::Regex => {
+                let regex = self.regex.as_ref()?;
+                let ranges = regex
+                    .find_iter(text)
+                    .map(|m| m.range())
+                    .filter(|range| !range.is_empty())
+                    .collect();
+
+                regex.is_match(text).then_some(Match { score: 0, ranges })
+            }
+        }
+    }
+
+    fn find_fuzzy(&self, text: &str) -> Option<Match> {
+        let (score, indices) = SkimMatcherV2::default()
+            .ignore_case()
+            .fuzzy_indices(text, &self.text)?;
+
+        // Turn the matched character indices into byte ranges,
+        // merging the characters that follow each other.
+        let mut ranges: Vec<Range<usize>> = Vec::new();
+        let mut indices = indices.into_iter().peekable();
+        for (char_index, (start, c)) in text.char_indices().enumerate() {
+            if indices.peek() != Some(&char_index) {
+                continue;
+            }
+            indices.next();
+
+            let end = start + c.len_utf8();
+            match ranges.last_mut() {
+                Some(last) if last.end == start => last.end = end,
+                _ => ranges.push(start..end),
+            }
+        }
+
+        Some(Match { score, ranges })
+    }
+}
+
+/// The entries matching a query, best first. Entries scoring the same keep
+/// their order in the history. Images only match an empty query.
+pub fn search(entries: &[HistoryEntry], query: &Query) -> Vec<SearchResult> {
+    let mut results: Vec<SearchResult> = entries
+        .iter()
+        .enumerate()
+        .filter_map(|(index, entry)| {
+            let found = match &entry.content {
+                ClipboardContent::Text(text) => query.find(text)?,
+                ClipboardContent::Image(_) if query.is_empty() => query.find("")?,
+                ClipboardContent::Image(_) => return None,
+            };
+
+            Some(SearchResult {
+                index,
+                entry: entry.clone(),
+                score: found.score,
+                ranges: found.ranges,
+            })
+        })
+        .collect();
+
+    results.sort_by_key(|result| std::cmp::Reverse(result.score));
+    results
+}
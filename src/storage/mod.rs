@@ -0,0 +1,71 @@
+//! Persistence of the history.
+//!
+//! The history is either saved as a single RON file, rewritten on every
+//! change, or kept in an SQLite database where each change only touches
+//! the rows of the entries it concerns.
+
+mod ron_file;
+mod sqlite;
+
+pub use ron_file::{
+    backup_path, load_history, recover_history, remove_backup, save_history, RonStore,
+};
+pub use sqlite::SqliteStore;
+
+use crate::encryption::Cipher;
+use crate::history::{HistoryChange, HistoryEntry};
+
+use anyhow::Result;
+use clap::ValueEnum;
+use serde::{Deserialize, Serialize};
+use std::path::Path;
+
+/// How the history is stored on disk.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize, ValueEnum)]
+#[serde(rename_all = "lowercase")]
+pub enum StorageBackend {
+    /// A RON file, rewritten on every change.
+    #[default]
+    Ron,
+    /// An SQLite database, suited to large histories.
+    Sqlite,
+}
+
+pub trait HistoryStore: Send {
+    /// Read the stored entries, most recent first.
+    fn load(&mut self, cipher: Option<&Cipher>) -> Result<Vec<HistoryEntry>>;
+
+    /// Replace the stored history with `entries`. The values are
+    /// encrypted when a cipher is given.
+    fn save(&mut self, entries: &[HistoryEntry], cipher: Option<&Cipher>) -> Result<()>;
+
+    /// Store the changes that turned the saved history into `entries`.
+    /// Backends that can not store them one by one save the whole history.
+    fn apply(
+        &mut self,
+        entries: &[HistoryEntry],
+        _changes: &[HistoryChange],
+        cipher: Option<&Cipher>,
+    ) -> Result<()> {
+        self.save(entries, cipher)
+    }
+
+    /// Whether some stored values are not encrypted.
+    fn has_plain_text(&mut self) -> Result<bool>;
+
+    /// Get back what can be saved from a history that can not be loaded.
+    fn recover(&mut self, cipher: Option<&Cipher>) -> Result<Vec<HistoryEntry>>;
+
+    /// Remove the copies of previous versions of the history, if any.
+    fn remove_backup(&mut self) -> Result<()> {
+        Ok(())
+    }
+}
+
+/// Open the history stored at `path` with a backend.
+pub fn open(backend: StorageBackend, path: &Path) -> Result<Box<dyn HistoryStore>> {
+    Ok(match backend {
+        StorageBackend::Ron => Box::new(RonStore::new(path)),
+        StorageBackend::Sqlite => Box::new(SqliteStore::open(path)?),
+    })
+}
@@ -0,0 +1,242 @@
+//! The history saved as a single RON file.
+
+use super::HistoryStore;
+use crate::encryption::{self, Cipher};
+use crate::history::{self, ClipboardContent, HistoryEntry};
+
+use anyhow::{anyhow, Context, Result};
+use std::fs;
+use std::io::{ErrorKind, Write};
+use std::path::{Path, PathBuf};
+
+const TEMP_SUFFIX: &str = "tmp";
+const BACKUP_SUFFIX: &str = "bak";
+
+/// Rewrites the whole history file on every save.
+pub struct RonStore {
+    path: PathBuf,
+}
+
+impl RonStore {
+    pub fn new(path: &Path) -> Self {
+        Self {
+            path: path.to_path_buf(),
+        }
+    }
+}
+
+impl HistoryStore for RonStore {
+    fn load(&mut self, cipher: Option<&Cipher>) -> Result<Vec<HistoryEntry>> {
+        load_history(&self.path, cipher)
+    }
+
+    fn save(&mut self, entries: &[HistoryEntry], cipher: Option<&Cipher>) -> Result<()> {
+        save_history(&self.path, entries, cipher)
+    }
+
+    fn has_plain_text(&mut self) -> Result<bool> {
+        match fs::read(&self.path) {
+            Ok(data) => Ok(!encryption::is_encrypted(&data)),
+            Err(e) if e.kind() == ErrorKind::NotFound => Ok(false),
+            Err(e) => Err(e).context(format!("Could not open \"{}\"", self.path.display())),
+        }
+    }
+
+    fn recover(&mut self, cipher: Option<&Cipher>) -> Result<Vec<HistoryEntry>> {
+        recover_history(&self.path, cipher)
+    }
+
+    fn remove_backup(&mut self) -> Result<()> {
+        remove_backup(&self.path)
+    }
+}
+
+/// Write the history entries to a RON file, creating its folder if needed.
+/// The file is encrypted when a cipher is given.
+///
+/// The entries are written to a temporary file which then replaces the
+/// history, so a crash never leaves it half written. The previous version
+/// of the history is kept as a backup.
+pub fn save_history(
+    history_path: &Path,
+    entries: &[HistoryEntry],
+    cipher: Option<&Cipher>,
+) -> Result<()> {
+    if let Some(dir) = history_path.parent() {
+        fs::create_dir_all(dir).context(format!(
+            "Could not create the \"{}\" folder.",
+            dir.display()
+        ))?;
+    }
+
+    let serialized_history =
+        ron::ser::to_string(entries).context("Could not serialize history when saving to file.")?;
+    let data = match cipher {
+        Some(cipher) => cipher.encrypt(serialized_history.as_bytes())?,
+        None => serialized_history.into_bytes(),
+    };
+
+    let temp_path = sibling_path(history_path, TEMP_SUFFIX);
+    let mut file = fs::OpenOptions::new()
+        .create(true)
+        .write(true)
+        .truncate(true)
+        .open(&temp_path)
+        .context(format!("Could not create or open {}", temp_path.display()))?;
+
+    file.write_all(&data)
+        .and_then(|()| file.sync_all())
+        .context(format!(
+            "Could not write serialized history to {}",
+            temp_path.display()
+        ))?;
+
+    if history_path.exists() {
+        rotate_backup(history_path)?;
+    }
+
+    fs::rename(&temp_path, history_path).context(format!(
+        "Could not replace {} with the new history",
+        history_path.display()
+    ))?;
+
+    // Make sure the rename itself reaches the disk
+    #[cfg(unix)]
+    if let Some(dir) = history_path.parent() {
+        let dir = if dir.as_os_str().is_empty() {
+            Path::new(".")
+        } else {
+            dir
+        };
+        fs::File::open(dir)
+            .and_then(|dir| dir.sync_all())
+            .context(format!("Could not sync the \"{}\" folder.", dir.display()))?;
+    }
+
+    Ok(())
+}
+
+/// Where the previous version of a history file is kept.
+pub fn backup_path(history_path: &Path) -> PathBuf {
+    sibling_path(history_path, BACKUP_SUFFIX)
+}
+
+/// Remove the backup of a history file, for example
+/// when it still holds entries that were deleted.
+pub fn remove_backup(history_path: &Path) -> Result<()> {
+    let path = backup_path(history_path);
+
+    match fs::remove_file(&path) {
+        Err(e) if e.kind() != ErrorKind::NotFound => {
+            Err(e).context(format!("Could not remove \"{}\"", path.display()))
+        }
+        _ => Ok(()),
+    }
+}
+
+/// Make the current history file the backup, without copying it when possible.
+fn rotate_backup(history_path: &Path) -> Result<()> {
+    let path = backup_path(history_path);
+    remove_backup(history_path)?;
+
+    fs::hard_link(history_path, &path)
+        .or_else(|_| fs::copy(history_path, &path).map(|_| ()))
+        .context(format!(
+            "Could not back up the history to \"{}\"",
+            path.display()
+        ))
+}
+
+/// Recover the entries of a history file that can not be loaded. They are
+/// read from the backup or, for a plain text history, from the part of the
+/// file that can still be parsed. The unreadable file is moved aside so
+/// that it is not overwritten.
+///
+/// Fails without touching anything when the history is encrypted and the
+/// backup can not be read either, as the key is then most likely wrong.
+pub fn recover_history(history_path: &Path, cipher: Option<&Cipher>) -> Result<Vec<HistoryEntry>> {
+    let data =
+        fs::read(history_path).context(format!("Could not open \"{}\"", history_path.display()))?;
+    let backup = load_history(&backup_path(history_path), cipher);
+
+    if backup.is_err() && encryption::is_encrypted(&data) {
+        return Err(anyhow!(
+            "The encrypted history can not be read and has no usable backup."
+        ));
+    }
+
+    let corrupt_path = sibling_path(history_path, &format!("corrupt-{}", history::now()));
+    fs::rename(history_path, &corrupt_path).context(format!(
+        "Could not move the unreadable history to \"{}\"",
+        corrupt_path.display()
+    ))?;
+    tracing::warn!(
+        "Moved the unreadable history to \"{}\" ...",
+        corrupt_path.display()
+    );
+
+    if let Ok(entries) = backup {
+        tracing::info!(
+            "Recovered {} entries from the history backup ...",
+            entries.len()
+        );
+        return Ok(entries);
+    }
+
+    let entries = salvage_entries(&String::from_utf8_lossy(&data));
+    tracing::info!(
+        "Recovered {} entries from the unreadable history ...",
+        entries.len()
+    );
+    Ok(entries)
+}
+
+/// Parse the entries of a truncated history, up to the last complete one.
+fn salvage_entries(data: &str) -> Vec<HistoryEntry> {
+    // Entries are separated by "),(" in the serialized list
+    let mut ends: Vec<usize> = data
+        .match_indices("),(")
+        .map(|(index, _)| index + 1)
+        .collect();
+    ends.push(data.len());
+
+    ends.into_iter()
+        .rev()
+        .find_map(|end| ron::de::from_str(&format!("{}]", &data[..end])).ok())
+        .unwrap_or_default()
+}
+
+/// A file next to the history, named after it with an extra extension.
+fn sibling_path(history_path: &Path, suffix: &str) -> PathBuf {
+    let mut name = history_path.as_os_str().to_owned();
+    name.push(".");
+    name.push(suffix);
+    PathBuf::from(name)
+}
+
+/// Read the history entries from a RON file, decrypting it if needed.
+/// A plain text file is still read when a cipher is given, it is encrypted
+/// the next time it is saved. History files written before entries carried
+/// metadata (a plain list of values) are migrated on the fly.
+pub fn load_history(history_path: &Path, cipher: Option<&Cipher>) -> Result<Vec<HistoryEntry>> {
+    let data =
+        fs::read(history_path).context(format!("Could not open \"{}\"", history_path.display()))?;
+
+    let data = if encryption::is_encrypted(&data) {
+        cipher
+            .ok_or_else(|| anyhow!("The history is encrypted but no key is configured."))?
+            .decrypt(&data)?
+    } else {
+        data
+    };
+    let data = String::from_utf8(data).context("The history file is not valid UTF-8.")?;
+
+    ron::de::from_str(&data).or_else(|error| {
+        let legacy_history: Vec<ClipboardContent> = ron::de::from_str(&data)
+            .map_err(|_| error)
+            .context("Error deserializing clipboard history.")?;
+
+        tracing::info!("Migrating history file from the legacy format ...");
+        Ok(HistoryEntry::migrate(legacy_history))
+    })
+}
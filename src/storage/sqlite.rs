@@ -0,0 +1,315 @@
+//! The history kept in an SQLite database, one row per entry.
+//!
+//! Rows are ordered by a rank, the most recent entry having the highest one,
+//! so moving an entry to the top does not renumber the others. When the
+//! history is encrypted, each value is encrypted on its own while the
+//! metadata of the entries stays readable.
+
+use super::HistoryStore;
+use crate::encryption::{self, Cipher};
+use crate::history::{ClipboardContent, HistoryChange, HistoryEntry};
+
+use anyhow::{anyhow, Context, Result};
+use rusqlite::{params, Connection, OptionalExtension, Row, Transaction};
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+use sha2::{Digest, Sha256};
+use std::fs;
+use std::path::{Path, PathBuf};
+
+const SCHEMA_VERSION: i64 = 1;
+
+const SCHEMA: &str = "
+    CREATE TABLE IF NOT EXISTS entries (
+        id INTEGER PRIMARY KEY,
+        rank INTEGER NOT NULL,
+        content BLOB NOT NULL,
+        content_hash TEXT,
+        first_copied INTEGER NOT NULL,
+        last_copied INTEGER NOT NULL,
+        copy_count INTEGER NOT NULL,
+        kind TEXT NOT NULL,
+        byte_size INTEGER NOT NULL,
+        pinned INTEGER NOT NULL,
+        source TEXT NOT NULL,
+        sensitive INTEGER NOT NULL
+    );
+    CREATE INDEX IF NOT EXISTS entries_rank ON entries (rank);
+    CREATE INDEX IF NOT EXISTS entries_last_copied ON entries (last_copied);
+    CREATE INDEX IF NOT EXISTS entries_content_hash ON entries (content_hash);
+";
+
+const COLUMNS: &str = "id, content, first_copied, last_copied, copy_count, \
+    kind, byte_size, pinned, source, sensitive";
+
+pub struct SqliteStore {
+    path: PathBuf,
+    connection: Connection,
+}
+
+impl SqliteStore {
+    /// Open the database, creating it and its folder if needed.
+    pub fn open(path: &Path) -> Result<Self> {
+        if let Some(dir) = path.parent() {
+            fs::create_dir_all(dir).context(format!(
+                "Could not create the \"{}\" folder.",
+                dir.display()
+            ))?;
+        }
+
+        let connection = Connection::open(path).context(format!(
+            "Could not open the history database \"{}\".",
+            path.display()
+        ))?;
+
+        // Removed entries are overwritten, so deleted secrets do not linger in free pages
+        connection
+            .execute_batch("PRAGMA secure_delete = ON;")
+            .and_then(|()| connection.execute_batch(SCHEMA))
+            .and_then(|()| connection.pragma_update(None, "user_version", SCHEMA_VERSION))
+            .context(format!(
+                "Could not set up the history database \"{}\".",
+                path.display()
+            ))?;
+
+        Ok(Self {
+            path: path.to_path_buf(),
+            connection,
+        })
+    }
+
+    fn insert(
+        transaction: &Transaction,
+        entry: &HistoryEntry,
+        rank: i64,
+        cipher: Option<&Cipher>,
+    ) -> Result<()> {
+        let (content, content_hash) = encode_content(&entry.content, cipher)?;
+
+        transaction
+            .prepare_cached(&format!(
+                "INSERT INTO entries (rank, content_hash, {COLUMNS})
+                 VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11, ?12)"
+            ))?
+            .execute(params![
+                rank,
+                content_hash,
+                entry.id,
+                content,
+                entry.first_copied,
+                entry.last_copied,
+                entry.copy_count,
+                to_text(&entry.kind)?,
+                entry.byte_size,
+                entry.pinned,
+                to_text(&entry.source)?,
+                entry.sensitive,
+            ])
+            .context(format!("Could not insert entry {}.", entry.id))?;
+
+        Ok(())
+    }
+
+    fn update(
+        transaction: &Transaction,
+        entry: &HistoryEntry,
+        cipher: Option<&Cipher>,
+    ) -> Result<()> {
+        let (content, content_hash) = encode_content(&entry.content, cipher)?;
+
+        transaction
+            .prepare_cached(
+                "UPDATE entries SET content = ?2, content_hash = ?3, first_copied = ?4,
+                 last_copied = ?5, copy_count = ?6, kind = ?7, byte_size = ?8, pinned = ?9,
+                 source = ?10, sensitive = ?11 WHERE id = ?1",
+            )?
+            .execute(params![
+                entry.id,
+                content,
+                content_hash,
+                entry.first_copied,
+                entry.last_copied,
+                entry.copy_count,
+                to_text(&entry.kind)?,
+                entry.byte_size,
+                entry.pinned,
+                to_text(&entry.source)?,
+                entry.sensitive,
+            ])
+            .context(format!("Could not update entry {}.", entry.id))?;
+
+        Ok(())
+    }
+
+    /// Replace every row with `entries`.
+    fn rewrite(
+        transaction: &Transaction,
+        entries: &[HistoryEntry],
+        cipher: Option<&Cipher>,
+    ) -> Result<()> {
+        transaction.execute("DELETE FROM entries", [])?;
+
+        for (index, entry) in entries.iter().enumerate() {
+            Self::insert(transaction, entry, (entries.len() - index) as i64, cipher)?;
+        }
+        Ok(())
+    }
+
+    /// Rank putting an entry above all the others.
+    fn top_rank(transaction: &Transaction) -> Result<i64> {
+        let max_rank: Option<i64> = transaction
+            .query_row("SELECT MAX(rank) FROM entries", [], |row| row.get(0))
+            .optional()?
+            .flatten();
+
+        Ok(max_rank.unwrap_or(0) + 1)
+    }
+}
+
+impl HistoryStore for SqliteStore {
+    fn load(&mut self, cipher: Option<&Cipher>) -> Result<Vec<HistoryEntry>> {
+        let mut statement = self
+            .connection
+            .prepare(&format!("SELECT {COLUMNS} FROM entries ORDER BY rank DESC"))?;
+
+        let rows = statement
+            .query_map([], |row| Ok(read_row(row)))
+            .context("Could not read the history database.")?;
+
+        rows.map(|row| row?.and_then(|(entry, content)| decode_entry(entry, &content, cipher)))
+            .collect()
+    }
+
+    fn save(&mut self, entries: &[HistoryEntry], cipher: Option<&Cipher>) -> Result<()> {
+        let transaction = self.connection.transaction()?;
+        Self::rewrite(&transaction, entries, cipher)?;
+        transaction
+            .commit()
+            .context("Could not save the history database.")
+    }
+
+    fn apply(
+        &mut self,
+        entries: &[HistoryEntry],
+        changes: &[HistoryChange],
+        cipher: Option<&Cipher>,
+    ) -> Result<()> {
+        let transaction = self.connection.transaction()?;
+
+        for change in changes {
+            match change {
+                HistoryChange::Insert { index: 0, entry } => {
+                    Self::insert(&transaction, entry, Self::top_rank(&transaction)?, cipher)?;
+                }
+                // Ranks can not express another position, the whole history is written
+                HistoryChange::Insert { .. } => {
+                    Self::rewrite(&transaction, entries, cipher)?;
+                    break;
+                }
+                HistoryChange::Remove { id } => {
+                    transaction.execute("DELETE FROM entries WHERE id = ?1", [id])?;
+                }
+                HistoryChange::MoveToTop { id } => {
+                    let rank = Self::top_rank(&transaction)?;
+                    transaction.execute(
+                        "UPDATE entries SET rank = ?2 WHERE id = ?1",
+                        params![id, rank],
+                    )?;
+                }
+                HistoryChange::Update(entry) => Self::update(&transaction, entry, cipher)?,
+                HistoryChange::Clear => {
+                    transaction.execute("DELETE FROM entries WHERE pinned = 0", [])?;
+                }
+            }
+        }
+
+        transaction
+            .commit()
+            .context("Could not save the history changes to the database.")
+    }
+
+    fn has_plain_text(&mut self) -> Result<bool> {
+        let mut statement = self.connection.prepare("SELECT content FROM entries")?;
+        let mut rows = statement.query([])?;
+
+        while let Some(row) = rows.next()? {
+            if !encryption::is_encrypted(&row.get::<_, Vec<u8>>(0)?) {
+                return Ok(true);
+            }
+        }
+        Ok(false)
+    }
+
+    /// SQLite recovers from crashes with its journal by itself, a database
+    /// that can still not be read is left for the user to inspect.
+    fn recover(&mut self, _cipher: Option<&Cipher>) -> Result<Vec<HistoryEntry>> {
+        Err(anyhow!(
+            "The history database \"{}\" can not be read, it was left untouched.",
+            self.path.display()
+        ))
+    }
+}
+
+/// Serialize a value, encrypting it when a cipher is given. Its hash is only
+/// stored in plain text databases, as it would tell encrypted values apart.
+fn encode_content(
+    content: &ClipboardContent,
+    cipher: Option<&Cipher>,
+) -> Result<(Vec<u8>, Option<String>)> {
+    let serialized = ron::ser::to_string(content).context("Could not serialize entry content.")?;
+
+    match cipher {
+        Some(cipher) => Ok((cipher.encrypt(serialized.as_bytes())?, None)),
+        None => {
+            let hash = format!("{:x}", Sha256::digest(serialized.as_bytes()));
+            Ok((serialized.into_bytes(), Some(hash)))
+        }
+    }
+}
+
+/// Read a row into an entry holding a placeholder content,
+/// along with the stored content to decode.
+fn read_row(row: &Row) -> Result<(HistoryEntry, Vec<u8>)> {
+    let entry = HistoryEntry {
+        id: row.get("id")?,
+        content: ClipboardContent::Text(String::new()),
+        first_copied: row.get("first_copied")?,
+        last_copied: row.get("last_copied")?,
+        copy_count: row.get("copy_count")?,
+        kind: from_text(&row.get::<_, String>("kind")?)?,
+        byte_size: row.get("byte_size")?,
+        pinned: row.get("pinned")?,
+        source: from_text(&row.get::<_, String>("source")?)?,
+        sensitive: row.get("sensitive")?,
+    };
+
+    Ok((entry, row.get("content")?))
+}
+
+fn decode_entry(
+    mut entry: HistoryEntry,
+    content: &[u8],
+    cipher: Option<&Cipher>,
+) -> Result<HistoryEntry> {
+    let content = if encryption::is_encrypted(content) {
+        cipher
+            .ok_or_else(|| anyhow!("The history is encrypted but no key is configured."))?
+            .decrypt(content)?
+    } else {
+        content.to_vec()
+    };
+
+    entry.content = ron::de::from_bytes(&content).context(format!(
+        "Could not deserialize the content of entry {}.",
+        entry.id
+    ))?;
+    Ok(entry)
+}
+
+fn to_text<T: Serialize>(value: &T) -> Result<String> {
+    ron::ser::to_string(value).context("Could not serialize entry metadata.")
+}
+
+fn from_text<T: DeserializeOwned>(text: &str) -> Result<T> {
+    ron::de::from_str(text).context("Could not deserialize entry metadata.")
+}
@@ -0,0 +1,305 @@
+//! Connections between the daemon and its clients.
+//!
+//! On Linux they go through Unix sockets stored in `$XDG_RUNTIME_DIR/clippy`,
+//! only accessible to the user running the daemon. TCP on localhost can still
+//! be used by setting `CLIPPY_TRANSPORT=tcp`, but then any local user can
+//! connect to the daemon.
+
+use anyhow::{anyhow, Context, Result};
+use std::fmt;
+use std::io::{self, Read, Write};
+use std::net::{Shutdown, TcpListener, TcpStream};
+#[cfg(unix)]
+use std::os::unix::net::{UnixListener, UnixStream};
+#[cfg(unix)]
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+pub const DAEMON_SOCKET_NAME: &str = "daemon.sock";
+/// Port the daemon listens on when using TCP.
+pub const DAEMON_TCP_PORT: u32 = 7879;
+
+/// Environment variable used to pick the transport, `unix` or `tcp`.
+const TRANSPORT_ENV_VAR: &str = "CLIPPY_TRANSPORT";
+
+/// Where an endpoint listens.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Transport {
+    #[cfg(unix)]
+    Unix(PathBuf),
+    Tcp(String),
+}
+
+pub enum Stream {
+    #[cfg(unix)]
+    Unix(UnixStream),
+    Tcp(TcpStream),
+}
+
+pub enum Listener {
+    #[cfg(unix)]
+    Unix(UnixListener),
+    Tcp(TcpListener),
+}
+
+impl Transport {
+    /// Where the daemon listens, for the current platform and environment.
+    pub fn daemon() -> Self {
+        Self::from_env(DAEMON_SOCKET_NAME, DAEMON_TCP_PORT)
+    }
+
+    /// The transport of an endpoint, using a Unix socket named `socket_name`
+    /// or TCP on `tcp_port` depending on the platform and `CLIPPY_TRANSPORT`.
+    pub fn from_env(socket_name: &str, tcp_port: u32) -> Self {
+        let tcp = Transport::Tcp(format!("127.0.0.1:{tcp_port}"));
+
+        match std::env::var(TRANSPORT_ENV_VAR).as_deref() {
+            Ok("tcp") => tcp,
+            #[cfg(unix)]
+            Ok("unix") => Transport::Unix(runtime_dir().join(socket_name)),
+            Ok(other) => {
+                tracing::warn!(
+                    "Unknown {TRANSPORT_ENV_VAR} value \"{other}\", using the default transport."
+                );
+                Self::default_for(socket_name, tcp)
+            }
+            Err(_) => Self::default_for(socket_name, tcp),
+        }
+    }
+
+    /// Unix sockets are the default on Linux.
+    #[cfg(target_os = "linux")]
+    fn default_for(socket_name: &str, _tcp: Transport) -> Self {
+        Transport::Unix(runtime_dir().join(socket_name))
+    }
+
+    #[cfg(not(target_os = "linux"))]
+    fn default_for(_socket_name: &str, tcp: Transport) -> Self {
+        tcp
+    }
+
+    pub fn connect(&self) -> Result<Stream> {
+        match self {
+            #[cfg(unix)]
+            Transport::Unix(path) => UnixStream::connect(path).map(Stream::Unix),
+            Transport::Tcp(address) => TcpStream::connect(address).map(Stream::Tcp),
+        }
+        .context(format!("Could not connect to \"{self}\"."))
+    }
+
+    pub fn bind(&self) -> Result<Listener> {
+        match self {
+            #[cfg(unix)]
+            Transport::Unix(path) => bind_unix_socket(path).map(Listener::Unix),
+            Transport::Tcp(address) => TcpListener::bind(address)
+                .map(Listener::Tcp)
+                .context(format!("Could not bind to \"{self}\".")),
+        }
+    }
+}
+
+impl fmt::Display for Transport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            #[cfg(unix)]
+            Transport::Unix(path) => write!(f, "unix:{}", path.display()),
+            Transport::Tcp(address) => write!(f, "tcp:{address}"),
+        }
+    }
+}
+
+impl Listener {
+    /// Wait for the next connection. On Unix sockets, connections coming
+    /// from another user than the one running this process are dropped,
+    /// and we keep waiting: they are not failures of the listener.
+    pub fn accept(&self) -> Result<Stream> {
+        match self {
+            #[cfg(unix)]
+            Listener::Unix(listener) => loop {
+                let (stream, _) = listener.accept().context("Could not accept connection.")?;
+                match check_peer_credentials(&stream) {
+                    Ok(()) => return Ok(Stream::Unix(stream)),
+                    Err(e) => tracing::warn!("{e:#} ..."),
+                }
+            },
+            Listener::Tcp(listener) => {
+                let (stream, _) = listener.accept().context("Could not accept connection.")?;
+                Ok(Stream::Tcp(stream))
+            }
+        }
+    }
+}
+
+impl Stream {
+    pub fn try_clone(&self) -> io::Result<Self> {
+        match self {
+            #[cfg(unix)]
+            Stream::Unix(stream) => stream.try_clone().map(Stream::Unix),
+            Stream::Tcp(stream) => stream.try_clone().map(Stream::Tcp),
+        }
+    }
+
+    pub fn set_read_timeout(&self, timeout: Option<Duration>) -> io::Result<()> {
+        match self {
+            #[cfg(unix)]
+            Stream::Unix(stream) => stream.set_read_timeout(timeout),
+            Stream::Tcp(stream) => stream.set_read_timeout(timeout),
+        }
+    }
+
+    pub fn set_write_timeout(&self, timeout: Option<Duration>) -> io::Result<()> {
+        match self {
+            #[cfg(unix)]
+            Stream::Unix(stream) => stream.set_write_timeout(timeout),
+            Stream::Tcp(stream) => stream.set_write_timeout(timeout),
+        }
+    }
+
+    pub fn shutdown(&self, how: Shutdown) -> io::Result<()> {
+        match self {
+            #[cfg(unix)]
+            Stream::Unix(stream) => stream.shutdown(how),
+            Stream::Tcp(stream) => stream.shutdown(how),
+        }
+    }
+}
+
+impl Read for Stream {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        match self {
+            #[cfg(unix)]
+            Stream::Unix(stream) => stream.read(buf),
+            Stream::Tcp(stream) => stream.read(buf),
+        }
+    }
+}
+
+impl Write for Stream {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        match self {
+            #[cfg(unix)]
+            Stream::Unix(stream) => stream.write(buf),
+            Stream::Tcp(stream) => stream.write(buf),
+        }
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        match self {
+            #[cfg(unix)]
+            Stream::Unix(stream) => stream.flush(),
+            Stream::Tcp(stream) => stream.flush(),
+        }
+    }
+}
+
+/// Folder holding the sockets, `$XDG_RUNTIME_DIR/clippy` or a
+/// per-user folder in the temp directory when it is not set.
+#[cfg(unix)]
+fn runtime_dir() -> PathBuf {
+    match std::env::var_os("XDG_RUNTIME_DIR") {
+        Some(dir) if !dir.is_empty() => PathBuf::from(dir).join("clippy"),
+        // SAFETY: geteuid can not fail
+        _ => std::env::temp_dir().join(format!("clippy-{}", unsafe { libc::geteuid() })),
+    }
+}
+
+#[cfg(unix)]
+fn bind_unix_socket(path: &Path) -> Result<UnixListener> {
+    use std::fs;
+    use std::os::unix::fs::{DirBuilderExt, PermissionsExt};
+
+    if let Some(dir) = path.parent() {
+        fs::DirBuilder::new()
+            .recursive(true)
+            .mode(0o700)
+            .create(dir)
+            .context(format!("Could not create \"{}\".", dir.display()))?;
+        fs::set_permissions(dir, fs::Permissions::from_mode(0o700)).context(format!(
+            "Could not restrict access to \"{}\".",
+            dir.display()
+        ))?;
+    }
+
+    if path.exists() {
+        if UnixStream::connect(path).is_ok() {
+            return Err(anyhow!(
+                "Another process is already listening on \"{}\".",
+                path.display()
+            ));
+        }
+        // Left behind by a process that did not exit cleanly
+        fs::remove_file(path).context(format!(
+            "Could not remove stale socket \"{}\".",
+            path.display()
+        ))?;
+    }
+
+    let listener =
+        UnixListener::bind(path).context(format!("Could not bind to \"{}\".", path.display()))?;
+    fs::set_permissions(path, fs::Permissions::from_mode(0o600)).context(format!(
+        "Could not restrict access to \"{}\".",
+        path.display()
+    ))?;
+
+    Ok(listener)
+}
+
+/// Refuse connections from other users, on top of the socket permissions.
+#[cfg(unix)]
+fn check_peer_credentials(stream: &UnixStream) -> Result<()> {
+    // SAFETY: geteuid can not fail
+    let own_uid = unsafe { libc::geteuid() };
+    let peer_uid = peer_uid(stream).context("Could not get the credentials of the peer.")?;
+
+    if peer_uid != own_uid {
+        return Err(anyhow!(
+            "Refused connection from user {peer_uid}, only user {own_uid} is allowed."
+        ));
+    }
+
+    Ok(())
+}
+
+#[cfg(any(target_os = "linux", target_os = "android"))]
+fn peer_uid(stream: &UnixStream) -> io::Result<libc::uid_t> {
+    use std::os::fd::AsRawFd;
+
+    let mut credentials = libc::ucred {
+        pid: 0,
+        uid: 0,
+        gid: 0,
+    };
+    let mut length = std::mem::size_of::<libc::ucred>() as libc::socklen_t;
+
+    // SAFETY: the buffer and its length match the ucred struct SO_PEERCRED writes
+    let result = unsafe {
+        libc::getsockopt(
+            stream.as_raw_fd(),
+            libc::SOL_SOCKET,
+            libc::SO_PEERCRED,
+            &mut credentials as *mut libc::ucred as *mut libc::c_void,
+            &mut length,
+        )
+    };
+
+    if result == 0 {
+        Ok(credentials.uid)
+    } else {
+        Err(io::Error::last_os_error())
+    }
+}
+
+#[cfg(all(unix, not(any(target_os = "linux", target_os = "android"))))]
+fn peer_uid(stream: &UnixStream) -> io::Result<libc::uid_t> {
+    use std::os::fd::AsRawFd;
+
+    let mut uid = 0;
+    let mut gid = 0;
+
+    // SAFETY: both pointers are valid for the duration of the call
+    if unsafe { libc::getpeereid(stream.as_raw_fd(), &mut uid, &mut gid) } == 0 {
+        Ok(uid)
+    } else {
+        Err(io::Error::last_os_error())
+    }
+}
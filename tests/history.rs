@@ -0,0 +1,87 @@
+//! Following the changes of the history, and matching copied
+//! values with their entry through the content index.
+
+use clippy::history::{
+    ClipboardContent, ContentIndex, History, HistoryChange, HistoryEntry, Selection,
+};
+
+fn text(text: &str) -> ClipboardContent {
+    ClipboardContent::Text(text.to_string())
+}
+
+fn entry(id: u64, value: &str) -> HistoryEntry {
+    HistoryEntry::new(id, text(value), Selection::Clipboard)
+}
+
+#[test]
+fn missed_deltas_are_detected_and_leave_the_history_untouched() {
+    let mut daemon = History::default();
+    let deltas: Vec<_> = ["one", "two", "three"]
+        .into_iter()
+        .enumerate()
+        .map(|(id, value)| {
+            daemon.commit(HistoryChange::Insert {
+                index: 0,
+                entry: entry(id as u64, value),
+            })
+        })
+        .collect();
+
+    let mut client = History::default();
+    assert!(client.apply(&deltas[0]));
+    let before = client.clone();
+
+    // The second delta was missed, the client has to fetch the history again
+    assert!(!client.apply(&deltas[2]));
+    assert_eq!(client, before);
+    // Deltas it already has are ignored
+    assert!(client.apply(&deltas[0]));
+    assert_eq!(client, before);
+
+    let mut resynced = daemon.clone();
+    assert!(resynced.apply(&deltas[2]));
+    assert_eq!(resynced, daemon);
+}
+
+#[test]
+fn content_index_follows_history_changes() {
+    let mut entries: Vec<HistoryEntry> = (0..1000)
+        .map(|id| entry(id, &format!("value {id}")))
+        .collect();
+    let mut index = ContentIndex::new(&entries, true);
+
+    assert_eq!(index.find(&text("value 42")), Some(42));
+    assert_eq!(index.find(&text("  value\n42 ")), Some(42));
+    assert_eq!(index.find(&text("value 1000")), None);
+
+    let mut updated = entries[42].clone();
+    updated.content = text("changed");
+    index.apply(&entries, &HistoryChange::Update(updated));
+    assert_eq!(index.find(&text("value 42")), None);
+    assert_eq!(index.find(&text("changed")), Some(42));
+
+    index.apply(&entries, &HistoryChange::Remove { id: 42 });
+    assert_eq!(index.find(&text("changed")), None);
+
+    index.apply(
+        &entries,
+        &HistoryChange::Insert {
+            index: 0,
+            entry: entry(1000, "value 1000"),
+        },
+    );
+    assert_eq!(index.find(&text("value 1000")), Some(1000));
+
+    entries[7].pinned = true;
+    index.apply(&entries, &HistoryChange::Clear);
+    assert_eq!(index.find(&text("value 7")), Some(7));
+    assert_eq!(index.find(&text("value 8")), None);
+}
+
+#[test]
+fn content_index_can_tell_whitespace_apart() {
+    let index = ContentIndex::new(&[entry(0, "a  b")], false);
+
+    assert_eq!(index.find(&text("a  b")), Some(0));
+    assert_eq!(index.find(&text("a b")), None);
+}
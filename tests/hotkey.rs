@@ -0,0 +1,29 @@
+//! Parsing of the global hotkey.
+
+use clippy::keys::Hotkey;
+
+#[test]
+fn hotkeys_are_parsed() {
+    let hotkey: Hotkey = "Ctrl+Alt+V".parse().unwrap();
+    assert_eq!(
+        hotkey,
+        Hotkey {
+            ctrl: true,
+            shift: false,
+            alt: true,
+            super_key: false,
+            keysym: 'v' as u32,
+        }
+    );
+
+    let hotkey: Hotkey = "super + F12".parse().unwrap();
+    assert!(hotkey.super_key);
+    assert_eq!(hotkey.keysym, 0xffc9);
+}
+
+#[test]
+fn hotkeys_need_a_modifier_and_a_known_key() {
+    for hotkey in ["V", "Ctrl+Unknown", "Hyper+V", "Ctrl+F13"] {
+        assert!(hotkey.parse::<Hotkey>().is_err(), "{hotkey}");
+    }
+}
@@ -0,0 +1,97 @@
+//! Parsing of the UI key bindings and the shortcuts they trigger.
+
+#[path = "../src/bin/ui/keybindings.rs"]
+mod keybindings;
+
+use eframe::egui::{self, Event, Key, Modifiers};
+use keybindings::{Action, KeyBindings, Shortcut};
+
+/// Press a key in a frame, returning the action it triggered
+/// and whether a text field would still receive text.
+fn press(
+    shortcuts: &[Shortcut],
+    key: Key,
+    modifiers: Modifiers,
+    typed: Option<&str>,
+) -> (Option<Action>, bool) {
+    let mut events = vec![Event::Key {
+        key,
+        physical_key: None,
+        pressed: true,
+        repeat: false,
+        modifiers,
+    }];
+    if key == Key::Space {
+        events.push(Event::Text(" ".to_string()));
+    }
+    let input = egui::RawInput {
+        events,
+        ..Default::default()
+    };
+
+    let (mut action, mut text_left) = (None, false);
+    let _ = egui::Context::default().run(input, |ctx| {
+        action = keybindings::pressed(ctx, shortcuts, typed);
+        text_left = ctx.input(|input| {
+            input
+                .events
+                .iter()
+                .any(|event| matches!(event, Event::Text(_)))
+        });
+    });
+    (action, text_left)
+}
+
+#[test]
+fn bindings_are_parsed_most_specific_first() {
+    let bindings = KeyBindings {
+        select_next: vec!["J".to_string(), "Ctrl+Shift+N".to_string()],
+        delete: vec!["Hyper+D".to_string(), "NotAKey".to_string()],
+        ..KeyBindings::default()
+    };
+    let shortcuts = bindings.shortcuts();
+
+    assert_eq!(
+        shortcuts[0].0,
+        egui::KeyboardShortcut::new(Modifiers::CTRL | Modifiers::SHIFT, Key::N)
+    );
+    assert!(shortcuts.iter().any(|&(shortcut, action)| {
+        shortcut == egui::KeyboardShortcut::new(Modifiers::CTRL, Key::Num1)
+            && action == Action::QuickPick(0)
+    }));
+    // Invalid bindings are left out
+    assert!(!shortcuts
+        .iter()
+        .any(|(_, action)| *action == Action::DeleteSelected));
+}
+
+#[test]
+fn shortcuts_only_give_way_to_text_they_would_edit() {
+    let shortcuts = KeyBindings::default().shortcuts();
+    let none = Modifiers::NONE;
+
+    assert_eq!(
+        press(&shortcuts, Key::Delete, none, None).0,
+        Some(Action::DeleteSelected)
+    );
+    assert_eq!(
+        press(&shortcuts, Key::Delete, none, Some("")).0,
+        Some(Action::DeleteSelected)
+    );
+    assert_eq!(press(&shortcuts, Key::Delete, none, Some("query")).0, None);
+    assert_eq!(press(&shortcuts, Key::J, none, Some("")).0, None);
+    assert_eq!(
+        press(&shortcuts, Key::ArrowDown, none, Some("query")).0,
+        Some(Action::SelectNext)
+    );
+
+    // Marking with Space does not type a space in the empty search box
+    assert_eq!(
+        press(&shortcuts, Key::Space, none, Some("")),
+        (Some(Action::ToggleMark), false)
+    );
+    assert_eq!(
+        press(&shortcuts, Key::Space, none, Some("two words")),
+        (None, true)
+    );
+}
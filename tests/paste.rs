@@ -0,0 +1,62 @@
+//! Pasting through an injector.
+
+use clippy::clipboard::MemoryClipboard;
+use clippy::history::{ClipboardContent, ImageEntry, Selection};
+use clippy::paste::{self, NoopInjector, PasteInjector};
+
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+#[test]
+fn paste_is_pressed_after_the_delay() {
+    let injector = NoopInjector::default();
+    let shared: Arc<Mutex<Box<dyn PasteInjector>>> =
+        Arc::new(Mutex::new(Box::new(injector.clone())));
+    let keystroke = "Ctrl+Shift+V".parse().unwrap();
+
+    let paste = paste::paste_later(shared, keystroke, Duration::from_millis(200));
+    assert!(injector.pastes().is_empty());
+
+    paste.join().unwrap();
+    assert_eq!(injector.pastes(), [keystroke]);
+}
+
+#[test]
+fn selected_entry_is_copied_before_being_pasted() {
+    let injector = NoopInjector::default();
+    let shared: Arc<Mutex<Box<dyn PasteInjector>>> =
+        Arc::new(Mutex::new(Box::new(injector.clone())));
+    let mut clipboard = MemoryClipboard::new();
+    let keystroke = "Ctrl+V".parse().unwrap();
+
+    // The UI is shown over the window to paste in, then an entry is selected
+    shared.lock().unwrap().remember_focus().unwrap();
+    let content = ClipboardContent::Text("selected".to_string());
+    let paste = paste::copy_and_paste(
+        &mut clipboard,
+        &content,
+        Arc::clone(&shared),
+        keystroke,
+        Duration::from_millis(200),
+    )
+    .unwrap();
+
+    assert_eq!(
+        clipboard.text(Selection::Clipboard).as_deref(),
+        Some("selected")
+    );
+    assert!(injector.pastes().is_empty());
+    paste.join().unwrap();
+    assert_eq!(injector.pastes(), [keystroke]);
+
+    // An image whose file is gone can not be copied, nor pasted
+    let missing = ClipboardContent::Image(ImageEntry {
+        hash: "missing".to_string(),
+        width: 1,
+        height: 1,
+        path: "/nonexistent/missing.png".into(),
+    });
+    let result = paste::copy_and_paste(&mut clipboard, &missing, shared, keystroke, Duration::ZERO);
+    assert!(result.is_err());
+    assert_eq!(injector.pastes(), [keystroke]);
+}
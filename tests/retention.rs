@@ -0,0 +1,29 @@
+//! Expiry of old and sensitive entries.
+
+use clippy::daemon::config::DaemonConfig;
+use clippy::daemon::retention::RetentionPolicy;
+use clippy::history::{self, ClipboardContent, HistoryEntry, Selection};
+
+use std::time::Duration;
+
+#[test]
+fn huge_lifetimes_never_expire() {
+    let config = DaemonConfig {
+        max_entry_age_secs: u64::MAX,
+        sensitive_entry_ttl_secs: u64::MAX,
+        ..DaemonConfig::default()
+    };
+    let policy = RetentionPolicy::new(&config);
+
+    let mut entry = HistoryEntry::new(
+        0,
+        ClipboardContent::Text("kept".to_string()),
+        Selection::Clipboard,
+    );
+    entry.sensitive = true;
+    let now = history::now();
+
+    assert_eq!(policy.expiry(&entry), None);
+    assert!(!policy.is_expired(&entry, now));
+    assert!(policy.next_sweep(&[entry], now) > Duration::from_secs(1));
+}
@@ -0,0 +1,63 @@
+//! Ranking and highlighting of search results.
+
+use clippy::history::{ClipboardContent, HistoryEntry, Selection};
+use clippy::search::{self, Query, SearchMode};
+
+fn entries(texts: &[&str]) -> Vec<HistoryEntry> {
+    texts
+        .iter()
+        .enumerate()
+        .map(|(id, text)| {
+            HistoryEntry::new(
+                id as u64,
+                ClipboardContent::Text(text.to_string()),
+                Selection::Clipboard,
+            )
+        })
+        .collect()
+}
+
+fn indices(entries: &[HistoryEntry], query: &str, mode: SearchMode) -> Vec<usize> {
+    search::search(entries, &Query::new(query, mode).unwrap())
+        .iter()
+        .map(|result| result.index)
+        .collect()
+}
+
+#[test]
+fn text_search_ignores_case_and_ranks_prefixes_first() {
+    let entries = entries(&["cargo build", "Build script", "rebuild", "build", "test"]);
+
+    assert_eq!(indices(&entries, "BUILD", SearchMode::Text), [3, 1, 0, 2]);
+    assert_eq!(indices(&entries, "", SearchMode::Text), [0, 1, 2, 3, 4]);
+}
+
+#[test]
+fn fuzzy_search_highlights_the_matched_characters() {
+    let entries = entries(&["git status", "docker compose up"]);
+
+    let results = search::search(&entries, &Query::new("dcu", SearchMode::Fuzzy).unwrap());
+
+    assert_eq!(results.len(), 1);
+    assert_eq!(results[0].index, 1);
+    for range in &results[0].ranges {
+        assert!(!range.is_empty());
+    }
+    let matched: String = results[0]
+        .ranges
+        .iter()
+        .map(|range| &"docker compose up"[range.clone()])
+        .collect();
+    assert_eq!(matched.to_lowercase(), "dcu");
+}
+
+#[test]
+fn regex_search_reports_every_match() {
+    let entries = entries(&["id 12 and 345", "no digits"]);
+
+    let results = search::search(&entries, &Query::new(r"\d+", SearchMode::Regex).unwrap());
+
+    assert_eq!(results.len(), 1);
+    assert_eq!(results[0].ranges, [3..5, 10..13]);
+    assert!(Query::new("(unclosed", SearchMode::Regex).is_err());
+}
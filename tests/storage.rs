@@ -0,0 +1,205 @@
+//! Reading and writing history files, encrypted or not.
+
+use clippy::encryption::{self, Cipher, Secret};
+use clippy::history::{ClipboardContent, History, HistoryChange, HistoryEntry, Selection};
+use clippy::storage::{self, HistoryStore, SqliteStore};
+
+use std::fs;
+
+fn entries() -> Vec<HistoryEntry> {
+    vec![HistoryEntry::new(
+        0,
+        ClipboardContent::Text("hunter2".to_string()),
+        Selection::Clipboard,
+    )]
+}
+
+fn passphrase_cipher(passphrase: &str) -> Cipher {
+    Cipher::new(Secret::Passphrase(passphrase.to_string()))
+}
+
+#[test]
+fn history_encrypted_with_a_passphrase_is_read_back() {
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("history.ron");
+    let entries = entries();
+
+    storage::save_history(&path, &entries, Some(&passphrase_cipher("correct horse"))).unwrap();
+
+    let data = fs::read(&path).unwrap();
+    assert!(encryption::is_encrypted(&data));
+    assert!(!String::from_utf8_lossy(&data).contains("hunter2"));
+
+    // A new cipher has to derive the key from the salt stored in the file
+    let loaded = storage::load_history(&path, Some(&passphrase_cipher("correct horse"))).unwrap();
+    assert_eq!(loaded, entries);
+}
+
+#[test]
+fn wrong_passphrase_is_refused() {
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("history.ron");
+
+    storage::save_history(&path, &entries(), Some(&passphrase_cipher("correct horse"))).unwrap();
+
+    assert!(storage::load_history(&path, Some(&passphrase_cipher("battery staple"))).is_err());
+    assert!(storage::load_history(&path, None).is_err());
+}
+
+#[test]
+fn plain_text_history_is_read_with_a_cipher() {
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("history.ron");
+    let entries = entries();
+
+    storage::save_history(&path, &entries, None).unwrap();
+
+    let cipher = Cipher::new(Secret::Key(encryption::generate_key()));
+    assert_eq!(
+        storage::load_history(&path, Some(&cipher)).unwrap(),
+        entries
+    );
+}
+
+#[test]
+fn legacy_history_is_migrated() {
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("history.ron");
+    fs::write(&path, r#"["newest", "oldest"]"#).unwrap();
+
+    let loaded = storage::load_history(&path, None).unwrap();
+    let ids: Vec<u64> = loaded.iter().map(|entry| entry.id).collect();
+    assert_eq!(ids, [1, 0]);
+    assert_eq!(
+        loaded[0].content,
+        ClipboardContent::Text("newest".to_string())
+    );
+}
+
+#[test]
+fn previous_history_is_kept_as_backup() {
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("history.ron");
+    let entries = entries();
+
+    storage::save_history(&path, &entries, None).unwrap();
+    storage::save_history(&path, &[], None).unwrap();
+
+    assert!(storage::load_history(&path, None).unwrap().is_empty());
+    assert_eq!(
+        storage::load_history(&storage::backup_path(&path), None).unwrap(),
+        entries
+    );
+    assert!(!dir.path().join("history.ron.tmp").exists());
+}
+
+#[test]
+fn corrupt_history_is_recovered_from_its_backup() {
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("history.ron");
+    let entries = entries();
+
+    storage::save_history(&path, &entries, None).unwrap();
+    storage::save_history(&path, &entries, None).unwrap();
+    fs::write(&path, "garbage").unwrap();
+
+    assert_eq!(storage::recover_history(&path, None).unwrap(), entries);
+    // The corrupt file is moved aside, not deleted
+    assert!(!path.exists());
+    assert_eq!(fs::read_dir(dir.path()).unwrap().count(), 2);
+}
+
+#[test]
+fn truncated_history_keeps_its_complete_entries() {
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("history.ron");
+    let entries: Vec<HistoryEntry> = ["first", "second"]
+        .into_iter()
+        .enumerate()
+        .map(|(id, text)| {
+            HistoryEntry::new(
+                id as u64,
+                ClipboardContent::Text(text.to_string()),
+                Selection::Clipboard,
+            )
+        })
+        .collect();
+
+    storage::save_history(&path, &entries, None).unwrap();
+    let data = fs::read_to_string(&path).unwrap();
+    fs::write(&path, &data[..data.len() - 10]).unwrap();
+
+    assert_eq!(storage::recover_history(&path, None).unwrap(), entries[..1]);
+}
+
+#[test]
+fn encrypted_history_without_backup_is_not_recovered() {
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("history.ron");
+    let cipher = Cipher::new(Secret::Key(encryption::generate_key()));
+
+    storage::save_history(&path, &entries(), Some(&cipher)).unwrap();
+
+    let wrong_cipher = Cipher::new(Secret::Key(encryption::generate_key()));
+    assert!(storage::recover_history(&path, Some(&wrong_cipher)).is_err());
+    assert!(path.exists());
+}
+
+#[test]
+fn sqlite_store_applies_changes() {
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("history.sqlite3");
+    let mut store = SqliteStore::open(&path).unwrap();
+    let mut history = History::new(Vec::new());
+
+    let mut changes = Vec::new();
+    for (id, text) in ["first", "second", "third"].into_iter().enumerate() {
+        let entry = HistoryEntry::new(
+            id as u64,
+            ClipboardContent::Text(text.to_string()),
+            Selection::Clipboard,
+        );
+        changes.push(HistoryChange::Insert { index: 0, entry });
+    }
+    let mut pinned = changes
+        .iter()
+        .find_map(|change| match change {
+            HistoryChange::Insert { entry, .. } if entry.id == 1 => Some(entry.clone()),
+            _ => None,
+        })
+        .unwrap();
+    pinned.pinned = true;
+    changes.extend([
+        HistoryChange::MoveToTop { id: 0 },
+        HistoryChange::Update(pinned),
+        HistoryChange::Remove { id: 2 },
+    ]);
+    for change in &changes {
+        history.commit(change.clone());
+    }
+
+    store.apply(&history.entries, &changes, None).unwrap();
+
+    let loaded = SqliteStore::open(&path).unwrap().load(None).unwrap();
+    assert_eq!(loaded, history.entries);
+    assert_eq!(
+        loaded.iter().map(|entry| entry.id).collect::<Vec<_>>(),
+        [0, 1]
+    );
+    assert!(loaded[1].pinned);
+}
+
+#[test]
+fn sqlite_store_encrypts_values() {
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("history.sqlite3");
+    let cipher = Cipher::new(Secret::Key(encryption::generate_key()));
+    let entries = entries();
+
+    let mut store = SqliteStore::open(&path).unwrap();
+    store.save(&entries, Some(&cipher)).unwrap();
+    assert!(!store.has_plain_text().unwrap());
+
+    assert_eq!(store.load(Some(&cipher)).unwrap(), entries);
+    assert!(store.load(None).is_err());
+}